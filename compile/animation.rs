@@ -0,0 +1,283 @@
+use std::{path::{Path, PathBuf}, fs, sync::{Arc, Mutex}};
+use cgmath::{Matrix4, SquareMatrix};
+use gltf::animation::util::ReadOutputs;
+
+use crate::{Config, Dirs, config::UpAxis, compress, cursor::Cursor, cache::{self, Cache}, low::{self, Kind, Section}, keyframes::{Channel, Encoding, Interpolation, Property, Track, Trs, Weights}};
+
+pub const COMPILED: &str = "./.compiled/animations/";
+
+/// Compiles every animation of a glTF file into its own `A` clip of keyframed joint channels,
+/// written to `<folder>/<file name>/<animation name>.low`.
+pub fn gltf(path: impl AsRef<Path>, dirs: &Dirs, conf: Config, cache: Arc<Mutex<Cache>>) -> Result<(), String> {
+    let path = path.as_ref();
+    let conf = conf.file(path)?;
+    if path.file_name().unwrap().to_string_lossy().starts_with('_') { return Ok(()) }
+    let output_dir = dirs.compiled_animations.join(path.strip_prefix(&dirs.animations).unwrap()).with_extension("");
+
+    let document = gltf::Gltf::open(path).map_err(|e| e.to_string())?;
+    let outputs: Vec<PathBuf> = document.animations()
+        .map(|v| output_dir.join(clip_name(&v)).with_extension("low"))
+        .collect();
+    if outputs.is_empty() { return Err("File has no animations".into()) }
+    let entry = cache::Entry::new(path, &crate::gltf_dependencies(path), format!("{:?}", conf));
+    let mut up_to_date = true;
+    for output in &outputs {
+        up_to_date &= cache.lock().unwrap().check(output, &entry);
+    }
+    if up_to_date { return Ok(()) }
+
+    let (gltf, buffers, _) = gltf::import(path).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&output_dir).map_err(|e| e.to_string())?;
+    let parents = node_parents(&gltf);
+    let joints = animated_joints(&gltf);
+    if joints.len() > u16::MAX as usize { return Err(format!("Animations can not have more than {} joints, found {}", u16::MAX, joints.len())) }
+    let joint_parents: Vec<Option<usize>> = joints.iter().map(|joint| {
+        let mut parent = parents[*joint];
+        while let Some(p) = parent {
+            if let Some(id) = joints.iter().position(|v| *v == p) { return Some(id) }
+            parent = parents[p];
+        }
+        None
+    }).collect();
+
+    for (animation, output) in gltf.animations().zip(outputs) {
+        println!("OutputPath: {}, {:?}", output.display(), conf);
+        let channels = read_channels(&animation, &buffers);
+        let weights = read_weights(&animation, &buffers)?;
+        let duration = channels.iter().map(|(_, c)| c.duration()).chain(weights.iter().map(|v| v.duration())).fold(0f32, f32::max);
+
+        let mut tracks: Vec<Track> = joints.iter().map(|joint| {
+            let node = gltf.nodes().nth(*joint).unwrap();
+            let (translation, rotation, scale) = node.transform().decomposed();
+            Track {
+                name: conf.rename_joint(node.name().unwrap_or("")),
+                rest: Trs { translation, rotation, scale },
+                channels: channels.iter().filter(|(node, _)| node == joint).map(|(_, c)| c.clone()).collect()
+            }
+        }).collect();
+
+        // Joints whose joint parent is not their node parent are relative to nodes
+        // outside of the skeleton, those are resampled at `conf.sample_rate`.
+        let resampled: Vec<usize> = (0..joints.len())
+            .filter(|id| joint_parents[*id].map(|v| joints[v]) != parents[joints[*id]])
+            .collect();
+        if !resampled.is_empty() {
+            let frames = (duration * conf.sample_rate).floor() as u32 + 1;
+            let times: Vec<f32> = (0..frames).map(|v| v as f32 / conf.sample_rate).collect();
+            let mut values = vec![[Vec::new(), Vec::new(), Vec::new()]; resampled.len()];
+            for time in &times {
+                let globals = global_matrices(&gltf, &parents, &channels, *time);
+                for (i, joint_id) in resampled.iter().enumerate() {
+                    let local = match joint_parents[*joint_id] {
+                        Some(parent) => globals[joints[parent]].invert().unwrap_or(Matrix4::identity()) * globals[joints[*joint_id]],
+                        None => globals[joints[*joint_id]]
+                    };
+                    let trs = Trs::from_matrix(local);
+                    let rotations: &mut Vec<[f32;4]> = &mut values[i][1];
+                    // Keep consecutive quaternions in the same hemisphere
+                    let rotation = match rotations.last() {
+                        Some(last) if dot(last, &trs.rotation) < 0. => trs.rotation.map(|v| -v),
+                        _ => trs.rotation
+                    };
+                    rotations.push(rotation);
+                    values[i][0].push([trs.translation[0], trs.translation[1], trs.translation[2], 0.]);
+                    values[i][2].push([trs.scale[0], trs.scale[1], trs.scale[2], 0.]);
+                }
+            }
+            for (joint_id, values) in resampled.iter().zip(values) {
+                tracks[*joint_id].channels = [Property::Translation, Property::Rotation, Property::Scale].into_iter().zip(values)
+                    .map(|(property, values)| Channel { property, interpolation: Interpolation::Linear, encoding: Encoding::Float, times: times.clone(), values })
+                    .collect();
+            }
+        }
+
+        convert(&mut tracks, &conf);
+        save(&output, &[("source", &path.to_string_lossy()), ("animation", animation.name().unwrap_or(""))], duration, tracks, &weights, &conf)?;
+        cache.lock().unwrap().insert(&output, entry.clone());
+    }
+    Ok(())
+}
+
+/// Compiles the keys `compiler.py` samples from an fbx clip inside Blender, every frame of every bone,
+/// into `<folder>/<file name>.low`. Blender writes them next to it as an uncompressed `<file name>.keys.low` clip.
+pub fn fbx(path: impl AsRef<Path>, dirs: &Dirs, conf: Config, cache: Arc<Mutex<Cache>>) -> Result<(), String> {
+    let path = path.as_ref();
+    let conf = conf.file(path)?;
+    if path.file_name().unwrap().to_string_lossy().starts_with('_') { return Ok(()) }
+    let output = dirs.compiled_animations.join(path.strip_prefix(&dirs.animations).unwrap()).with_extension("low");
+    let keys = output.with_extension("keys.low");
+    if !keys.is_file() { return Err("Keys not exported, run blender --background --python compiler.py".into()) }
+    let entry = cache::Entry::new(path, &[path.to_path_buf(), keys.clone()], format!("{:?}", conf));
+    if cache.lock().unwrap().check(&output, &entry) { return Ok(()) }
+
+    println!("OutputPath: {}, {:?}", output.display(), conf);
+    let file = low::File::read(&keys, Kind::Animation).map_err(|e| e.to_string())?;
+    let mut cursor = file.require(Section::Channels).map_err(|e| e.to_string())?;
+    let duration = cursor.read_f32();
+    let mut tracks = (0..cursor.read_u16()).map(|_| Track::read(&mut cursor)).collect::<Result<Vec<Track>, low::Error>>().map_err(|e| e.to_string())?;
+    for track in &mut tracks {
+        track.name = conf.rename_joint(&track.name);
+    }
+    save(&output, &[("source", &path.to_string_lossy())], duration, tracks, &[], &conf)?;
+    cache.lock().unwrap().insert(&output, entry);
+    Ok(())
+}
+
+/// Compresses the tracks of a clip, reports its size and error and saves it to `output`.
+fn save(output: &Path, metadata: &[(&str, &str)], duration: f32, mut tracks: Vec<Track>, weights: &[Weights], conf: &Config) -> Result<(), String> {
+    let original = tracks.clone();
+    compress::tracks(&mut tracks, conf.animation_tolerance, conf.quantize_rotations);
+    let original_size = channels_section(duration, &original).len();
+    let b = channels_section(duration, &tracks);
+    let mut cursor = Cursor::new(b[6..].to_vec());
+    let decoded = tracks.iter().map(|_| Track::read(&mut cursor)).collect::<Result<Vec<Track>, low::Error>>().map_err(|e| e.to_string())?;
+    let (max_error, max_rotation_error) = compress::max_error(&original, &decoded, conf.sample_rate);
+    println!("Clip: {}, {} -> {} bytes ({:.0}%), max error {:.6}, max rotation error {:.6} rad",
+        output.display(), original_size, b.len(), b.len() as f32 * 100. / original_size as f32, max_error, max_rotation_error);
+
+    let mut f = low::Writer::new(Kind::Animation);
+    f.metadata(metadata);
+    f.section(Section::Channels, b);
+    if !weights.is_empty() {
+        let mut b = (weights.len() as u16).to_be_bytes().to_vec();
+        for track in weights {
+            track.write(&mut b);
+        }
+        println!("Weights: {}, {} meshes, {} keys", output.display(), weights.len(), weights.iter().map(|v| v.times.len()).sum::<usize>());
+        f.section(Section::Weights, b);
+    }
+    f.save(output).map_err(|e| e.to_string())
+}
+
+/// Applies the scale and axis conversion of `conf` to the joint local transforms,
+/// the same conjugation the mesh compiler applies to inverse bind matrices.
+fn convert(tracks: &mut [Track], conf: &Config) {
+    if conf.scale == 1. && conf.up_axis == UpAxis::Y { return }
+    for track in tracks {
+        track.rest = conf.convert_trs(track.rest);
+        for channel in &mut track.channels {
+            for v in &mut channel.values {
+                *v = conf.convert(channel.property, *v);
+            }
+        }
+    }
+}
+
+fn channels_section(duration: f32, tracks: &[Track]) -> Vec<u8> {
+    let mut b = duration.to_be_bytes().to_vec();
+    b.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+    for track in tracks {
+        track.write(&mut b);
+    }
+    b
+}
+
+fn clip_name(animation: &gltf::Animation) -> String {
+    match animation.name() {
+        Some(name) if !name.is_empty() => name.replace(['/', '\\', ':', '|', '.'], "_"),
+        _ => format!("animation{}", animation.index())
+    }
+}
+
+/// Joints of the first skin, or every node with an animated transform when the file has no skin.
+fn animated_joints(gltf: &gltf::Document) -> Vec<usize> {
+    if let Some(skin) = gltf.skins().next() {
+        return skin.joints().map(|v| v.index()).collect()
+    }
+    let mut joints: Vec<usize> = gltf.animations()
+        .flat_map(|a| a.channels()
+            .filter(|c| c.target().property() != gltf::animation::Property::MorphTargetWeights)
+            .map(|c| c.target().node().index()).collect::<Vec<_>>())
+        .collect();
+    joints.sort();
+    joints.dedup();
+    joints
+}
+
+fn node_parents(gltf: &gltf::Document) -> Vec<Option<usize>> {
+    let mut parents = vec![None; gltf.nodes().len()];
+    for node in gltf.nodes() {
+        for child in node.children() {
+            parents[child.index()] = Some(node.index());
+        }
+    }
+    parents
+}
+
+fn read_channels(animation: &gltf::Animation, buffers: &[gltf::buffer::Data]) -> Vec<(usize, Channel)> {
+    let mut channels = Vec::new();
+    for channel in animation.channels() {
+        let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+        let times: Vec<f32> = match reader.read_inputs() {
+            Some(v) => v.collect(),
+            None => continue
+        };
+        if times.is_empty() { continue }
+        let (property, values): (Property, Vec<[f32;4]>) = match reader.read_outputs() {
+            Some(ReadOutputs::Translations(v)) => (Property::Translation, v.map(|v| [v[0], v[1], v[2], 0.]).collect()),
+            Some(ReadOutputs::Rotations(v)) => (Property::Rotation, v.into_f32().collect()),
+            Some(ReadOutputs::Scales(v)) => (Property::Scale, v.map(|v| [v[0], v[1], v[2], 0.]).collect()),
+            _ => continue
+        };
+        let interpolation = interpolation(&channel);
+        channels.push((channel.target().node().index(), Channel { property, interpolation, encoding: Encoding::Float, times, values }));
+    }
+    channels
+}
+
+/// Morph target weight channels, named after the glTF mesh of their node like the mesh compiler does.
+fn read_weights(animation: &gltf::Animation, buffers: &[gltf::buffer::Data]) -> Result<Vec<Weights>, String> {
+    let mut res = Vec::new();
+    for channel in animation.channels() {
+        let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+        let (Some(times), Some(ReadOutputs::MorphTargetWeights(values))) = (reader.read_inputs(), reader.read_outputs()) else { continue };
+        let Some(mesh) = channel.target().node().mesh() else { continue };
+        let (times, values): (Vec<f32>, Vec<f32>) = (times.collect(), values.into_f32().collect());
+        let interpolation = interpolation(&channel);
+        let keys = times.len() * if interpolation == Interpolation::CubicSpline { 3 } else { 1 };
+        if times.is_empty() || values.is_empty() { continue }
+        if values.len() % keys != 0 {
+            return Err(format!("Weights of mesh {} have {} values for {} keys", mesh.name().unwrap_or(""), values.len(), keys))
+        }
+        res.push(Weights { mesh: crate::mesh_name(&mesh), interpolation, targets: values.len() / keys, times, values });
+    }
+    Ok(res)
+}
+
+fn interpolation(channel: &gltf::animation::Channel) -> Interpolation {
+    match channel.sampler().interpolation() {
+        gltf::animation::Interpolation::Step => Interpolation::Step,
+        gltf::animation::Interpolation::Linear => Interpolation::Linear,
+        gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline
+    }
+}
+
+/// World matrices of every node at `time`, animated channels override the node transforms.
+fn global_matrices(gltf: &gltf::Document, parents: &[Option<usize>], channels: &[(usize, Channel)], time: f32) -> Vec<Matrix4<f32>> {
+    let mut trs: Vec<Trs> = gltf.nodes().map(|v| {
+        let (translation, rotation, scale) = v.transform().decomposed();
+        Trs { translation, rotation, scale }
+    }).collect();
+    for (node, channel) in channels {
+        trs[*node].set(channel.property, channel.sample(time));
+    }
+    let locals: Vec<Matrix4<f32>> = trs.iter().map(|v| v.matrix()).collect();
+    let mut globals: Vec<Option<Matrix4<f32>>> = vec![None; locals.len()];
+    for node in 0..locals.len() {
+        global_matrix(node, parents, &locals, &mut globals);
+    }
+    globals.into_iter().map(|v| v.unwrap()).collect()
+}
+fn global_matrix(node: usize, parents: &[Option<usize>], locals: &[Matrix4<f32>], globals: &mut [Option<Matrix4<f32>>]) -> Matrix4<f32> {
+    if let Some(v) = globals[node] { return v }
+    let res = match parents[node] {
+        Some(parent) => global_matrix(parent, parents, locals, globals) * locals[node],
+        None => locals[node]
+    };
+    globals[node] = Some(res);
+    res
+}
+
+fn dot(a: &[f32;4], b: &[f32;4]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
@@ -0,0 +1,115 @@
+use std::{path::{Path, PathBuf}, collections::{HashMap, HashSet}, fs};
+
+/// Bump when the compiler output changes, so every cached asset gets rebuilt.
+pub const COMPILER_VERSION: u32 = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub source: PathBuf,
+    pub hash: u64,
+    pub settings: String,
+    pub compiler: String
+}
+impl Entry {
+    pub fn new(source: impl AsRef<Path>, dependencies: &[PathBuf], settings: String) -> Self {
+        Self {
+            source: source.as_ref().to_path_buf(),
+            hash: hash_files(dependencies),
+            settings,
+            compiler: format!("{}.{}", COMPILER_VERSION, crate::low::VERSION)
+        }
+    }
+}
+
+/// Manifest of compiled outputs, one tab separated line per output:
+/// `output, source, source hash, compiler version, compile.conf settings`.
+#[derive(Default)]
+pub struct Cache {
+    entries: HashMap<PathBuf, Entry>,
+    seen: HashSet<PathBuf>,
+    /// Sources of the outputs checked by this run, by whether they were up to date, and of the outputs rebuilt.
+    fresh: HashSet<PathBuf>,
+    stale: HashSet<PathBuf>,
+    rebuilt: HashSet<PathBuf>
+}
+impl Cache {
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let mut cache = Self::default();
+        let data = match fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(_) => return cache
+        };
+        for line in data.lines() {
+            let spl: Vec<&str> = line.splitn(5, '\t').collect();
+            if spl.len() != 5 { continue }
+            let hash = match u64::from_str_radix(spl[2], 16) {
+                Ok(v) => v,
+                Err(_) => continue
+            };
+            cache.entries.insert(spl[0].into(), Entry {
+                source: spl[1].into(),
+                hash,
+                compiler: spl[3].to_string(),
+                settings: spl[4].to_string()
+            });
+        }
+        cache
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut outputs: Vec<&PathBuf> = self.entries.keys().collect();
+        outputs.sort();
+        let mut data = String::new();
+        for output in outputs {
+            let e = &self.entries[output];
+            data += &format!("{}\t{}\t{:016x}\t{}\t{}\n", output.display(), e.source.display(), e.hash, e.compiler, e.settings);
+        }
+        fs::write(path, data)
+    }
+    /// Marks the output as still produced by this run and returns true if it is up to date.
+    pub fn check(&mut self, output: impl AsRef<Path>, entry: &Entry) -> bool {
+        self.seen.insert(output.as_ref().to_path_buf());
+        let fresh = output.as_ref().is_file() && self.entries.get(output.as_ref()) == Some(entry);
+        if fresh { &mut self.fresh } else { &mut self.stale }.insert(entry.source.clone());
+        fresh
+    }
+    pub fn insert(&mut self, output: impl AsRef<Path>, entry: Entry) {
+        self.rebuilt.insert(entry.source.clone());
+        self.seen.insert(output.as_ref().to_path_buf());
+        self.entries.insert(output.as_ref().to_path_buf(), entry);
+    }
+    /// Source assets rebuilt by this run, a glTF counts once for its mesh and scene.
+    pub fn rebuilt(&self) -> usize {
+        self.rebuilt.len()
+    }
+    /// Source assets whose every output was up to date.
+    pub fn up_to_date(&self) -> usize {
+        self.fresh.difference(&self.stale).count()
+    }
+    /// Deletes outputs that were not produced by this run, their source was removed or renamed.
+    /// Outputs of sources under `failed`, files or directories whose settings could not be read, are kept.
+    pub fn remove_stale(&mut self, failed: &[PathBuf]) -> Vec<PathBuf> {
+        let stale: Vec<PathBuf> = self.entries.iter()
+            .filter(|(output, entry)| !self.seen.contains(*output) && !failed.iter().any(|v| entry.source.starts_with(v)))
+            .map(|(output, _)| output.clone())
+            .collect();
+        for output in &stale {
+            self.entries.remove(output);
+            fs::remove_file(output).unwrap_or_default();
+        }
+        stale
+    }
+}
+
+/// FNV-1a over the content of every file, missing files hash as empty.
+pub fn hash_files(paths: &[PathBuf]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for path in paths {
+        for b in fs::read(path).unwrap_or_default() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash ^= 0xff;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
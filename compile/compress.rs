@@ -0,0 +1,173 @@
+use cgmath::{InnerSpace, Vector4, VectorSpace};
+use crate::keyframes::{Channel, Encoding, Interpolation, Property, Track, array_to_quaternion, quaternion_to_array};
+
+/// Difference between two values of `property`, the angle in radians for rotations
+/// and the largest component difference otherwise.
+pub fn error(property: Property, a: [f32;4], b: [f32;4]) -> f32 {
+    match property {
+        Property::Rotation => {
+            // Angle between the quaternions with atan2, acos is not precise enough near 1
+            let sign = if a.iter().zip(&b).map(|(a, b)| a * b).sum::<f32>() < 0. { -1. } else { 1. };
+            let length = |f: &dyn Fn(f32, f32) -> f32| a.iter().zip(&b).map(|(a, b)| f(*a, *b).powi(2)).sum::<f32>().sqrt();
+            4. * length(&|a, b| a - sign * b).atan2(length(&|a, b| a + sign * b))
+        }
+        _ => a.iter().zip(&b).take(3).map(|(a, b)| (a - b).abs()).fold(0., f32::max)
+    }
+}
+
+/// Moves constant channels into the rest pose, removes keys that interpolation reproduces
+/// within `tolerance` and quantizes step and linear rotations.
+pub fn tracks(tracks: &mut [Track], tolerance: f32, quantize_rotations: bool) {
+    for track in tracks {
+        let mut channels = Vec::new();
+        for mut channel in std::mem::take(&mut track.channels) {
+            if is_constant(&channel, tolerance) {
+                track.rest.set(channel.property, channel.value(0));
+                continue
+            }
+            reduce_keys(&mut channel, tolerance);
+            if quantize_rotations && channel.property == Property::Rotation && channel.interpolation != Interpolation::CubicSpline {
+                channel.encoding = Encoding::SmallestThree;
+            }
+            channels.push(channel);
+        }
+        track.channels = channels;
+    }
+}
+
+/// Largest (translation or scale, rotation) difference between the clips, sampled at
+/// every key and `sample_rate` times per second.
+pub fn max_error(original: &[Track], compressed: &[Track], sample_rate: f32) -> (f32, f32) {
+    let mut res = (0f32, 0f32);
+    for (a, b) in original.iter().zip(compressed) {
+        let mut times: Vec<f32> = a.channels.iter().flat_map(|v| v.times.iter().copied()).collect();
+        let duration = a.channels.iter().map(|v| v.duration()).fold(0., f32::max);
+        times.extend((0..=(duration * sample_rate) as u32).map(|v| v as f32 / sample_rate));
+        for time in times {
+            let (a, b) = (a.sample_trs(time), b.sample_trs(time));
+            for property in [Property::Translation, Property::Rotation, Property::Scale] {
+                let e = error(property, a.get(property), b.get(property));
+                match property {
+                    Property::Rotation => res.1 = res.1.max(e),
+                    _ => res.0 = res.0.max(e)
+                }
+            }
+        }
+    }
+    res
+}
+
+fn is_constant(channel: &Channel, tolerance: f32) -> bool {
+    let first = channel.value(0);
+    let tangents_flat = channel.interpolation != Interpolation::CubicSpline
+        || channel.values.iter().enumerate().filter(|(i, _)| i % 3 != 1).all(|(_, v)| v.iter().all(|c| c.abs() <= tolerance));
+    tangents_flat && (0..channel.times.len()).all(|k| error(channel.property, first, channel.value(k)) <= tolerance)
+}
+
+/// Greedily drops keys while interpolating between the kept neighbours stays within `tolerance`
+/// of every dropped key. Cubic spline channels are kept as they are.
+fn reduce_keys(channel: &mut Channel, tolerance: f32) {
+    let (times, values) = (&channel.times, &channel.values);
+    if times.len() < 3 { return }
+    let last = times.len() - 1;
+    let mut keep = vec![0];
+    match channel.interpolation {
+        Interpolation::CubicSpline => return,
+        Interpolation::Step => for k in 1..last {
+            if error(channel.property, values[*keep.last().unwrap()], values[k]) > tolerance { keep.push(k) }
+        },
+        Interpolation::Linear => for k in 1..last {
+            let a = *keep.last().unwrap();
+            let dt = times[k + 1] - times[a];
+            let fits = (a + 1..=k).all(|i| {
+                let s = if dt > 0. { (times[i] - times[a]) / dt } else { 0. };
+                error(channel.property, interpolate(channel.property, values[a], values[k + 1], s), values[i]) <= tolerance
+            });
+            if !fits { keep.push(k) }
+        }
+    }
+    keep.push(last);
+    channel.times = keep.iter().map(|k| times[*k]).collect();
+    channel.values = keep.iter().map(|k| values[*k]).collect();
+}
+
+fn interpolate(property: Property, a: [f32;4], b: [f32;4], s: f32) -> [f32;4] {
+    match property {
+        Property::Rotation => quaternion_to_array(array_to_quaternion(a).slerp(array_to_quaternion(b), s).normalize()),
+        _ => Vector4::from(a).lerp(Vector4::from(b), s).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cursor::Cursor, keyframes::Trs};
+
+    fn channel(property: Property, values: Vec<[f32;4]>) -> Channel {
+        let times = (0..values.len()).map(|v| v as f32 / 30.).collect();
+        Channel { property, interpolation: Interpolation::Linear, encoding: Encoding::Float, times, values }
+    }
+    fn track(channels: Vec<Channel>) -> Track {
+        Track { name: "joint".into(), rest: Trs { translation: [0.;3], rotation: [0., 0., 0., 1.], scale: [1.;3] }, channels }
+    }
+    /// Writes and reads the tracks back like the game does.
+    fn decode(tracks: &[Track]) -> Vec<Track> {
+        let mut b = Vec::new();
+        for track in tracks {
+            track.write(&mut b);
+        }
+        let mut cursor = Cursor::new(b);
+        tracks.iter().map(|_| Track::read(&mut cursor).unwrap()).collect()
+    }
+    fn curves() -> Vec<Channel> {
+        let frames = 0..120;
+        vec![
+            channel(Property::Translation, frames.clone().map(|v| { let t = v as f32 / 30.; [t.sin(), (t * 3.).cos() * 0.2, t * 0.5, 0.] }).collect()),
+            channel(Property::Rotation, frames.map(|v| { let a = (v as f32 / 20.).sin() * 1.5; [0., (a / 2.).sin(), 0., (a / 2.).cos()] }).collect())
+        ]
+    }
+
+    #[test]
+    fn reduced_keys_stay_within_tolerance() {
+        for tolerance in [0.01, 0.001, 0.0001] {
+            let original = vec![track(curves())];
+            let mut tracks = original.clone();
+            super::tracks(&mut tracks, tolerance, false);
+            if tolerance >= 0.001 {
+                assert!(tracks[0].channels.iter().all(|v| v.times.len() < 120));
+            }
+            let (error, rotation_error) = max_error(&original, &decode(&tracks), 240.);
+            assert!(error <= tolerance, "{} > {}", error, tolerance);
+            assert!(rotation_error <= tolerance * 1.01, "{} > {}", rotation_error, tolerance);
+        }
+    }
+
+    #[test]
+    fn quantized_rotations_add_only_the_quantization_error() {
+        let tolerance = 0.001;
+        let original = vec![track(curves())];
+        let mut tracks = original.clone();
+        super::tracks(&mut tracks, tolerance, true);
+        assert_eq!(tracks[0].channels[1].encoding, Encoding::SmallestThree);
+        let (error, rotation_error) = max_error(&original, &decode(&tracks), 240.);
+        assert!(error <= tolerance);
+        assert!(rotation_error <= tolerance + 2e-4, "{}", rotation_error);
+    }
+
+    #[test]
+    fn constant_channels_move_into_the_rest_pose() {
+        let rotation = [0., 0.38268343, 0., 0.9238795];
+        let mut tracks = vec![track(vec![
+            channel(Property::Translation, (0..60).map(|v| [1., 2. + (v % 2) as f32 * 1e-4, 3., 0.]).collect()),
+            channel(Property::Rotation, vec![rotation; 60]),
+            channel(Property::Scale, vec![[2., 2., 2., 0.]; 1])
+        ])];
+        super::tracks(&mut tracks, 0.001, true);
+        assert!(tracks[0].channels.is_empty());
+        assert_eq!(tracks[0].rest, Trs { translation: [1., 2., 3.], rotation, scale: [2., 2., 2.] });
+        // Changes above the tolerance keep the channel
+        let mut tracks = vec![track(vec![channel(Property::Translation, (0..60).map(|v| [0., (v % 2) as f32 * 0.01, 0., 0.]).collect())])];
+        super::tracks(&mut tracks, 0.001, true);
+        assert_eq!(tracks[0].channels.len(), 1);
+    }
+}
@@ -0,0 +1,258 @@
+use std::{path::Path, fs, str::FromStr};
+use cgmath::{Deg, Matrix3, Matrix4, One, Quaternion, Rotation, Rotation3, Vector3};
+
+use crate::{low::{TextureCompression, TextureKind}, keyframes::{Property, Trs, array_to_quaternion, quaternion_to_array}};
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VertexType {
+    Basic,
+    /// Normal and UV for textured static meshes.
+    N,
+    NJW,
+    /// Normal, tangent and UV for normal mapped static meshes.
+    NT,
+    /// NT with joints and weights.
+    NTJW
+}
+impl VertexType {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            VertexType::Basic => "Basic",
+            VertexType::N => "N",
+            VertexType::NJW => "NJW",
+            VertexType::NT => "NT",
+            VertexType::NTJW => "NTJW"
+        }
+    }
+    pub const fn skinned(&self) -> bool {
+        matches!(self, VertexType::NJW | VertexType::NTJW)
+    }
+    pub const fn tangents(&self) -> bool {
+        matches!(self, VertexType::NT | VertexType::NTJW)
+    }
+    /// The same attributes without joints and weights, for meshes that are not skinned.
+    pub const fn without_skin(&self) -> Self {
+        match self {
+            VertexType::NJW => VertexType::N,
+            VertexType::NTJW => VertexType::NT,
+            v => *v
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpAxis {
+    Y, Z
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JointCase {
+    Keep, Lower, Upper
+}
+
+/// Compile settings, every directory inherits the `compile.conf` of its parents and an asset
+/// can override them with `<file name>.conf`, for example `mesh.gltf.conf`.
+/// Lines are `Key=Value`, empty lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub vertex_type: VertexType,
+    /// Uniform scale applied to meshes and animations.
+    pub scale: f32,
+    /// Up axis of the source assets, converted to Y-up.
+    pub up_axis: UpAxis,
+    /// `JointReplace=<from> -> <to>` rules applied in order, an empty value clears the inherited rules.
+    pub joint_replace: Vec<(String, String)>,
+    pub joint_case: JointCase,
+    pub texture_kind: TextureKind,
+    pub texture_compression: TextureCompression,
+    pub mipmaps: bool,
+    /// Frames per second animations are sampled at.
+    pub sample_rate: f32,
+    /// Largest error animation key reduction may introduce, in model units for translation and scale and radians for rotation.
+    pub animation_tolerance: f32,
+    pub quantize_rotations: bool,
+    /// Joint influences below this share of the vertex weight are dropped before renormalizing.
+    pub min_weight: f32,
+    /// Reorders triangles and vertices of meshes for the vertex cache and overdraw.
+    pub optimize_mesh: bool,
+    /// Triangle ratio of every generated level of detail, decreasing.
+    pub lod_ratios: Vec<f32>,
+    /// Camera distance to the mesh bounds from which each level of detail is drawn.
+    pub lod_distances: Vec<f32>
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            vertex_type: VertexType::NJW,
+            scale: 1.,
+            up_axis: UpAxis::Y,
+            joint_replace: Vec::new(),
+            joint_case: JointCase::Keep,
+            texture_kind: TextureKind::Color,
+            texture_compression: TextureCompression::None,
+            mipmaps: true,
+            sample_rate: 30.,
+            animation_tolerance: 0.001,
+            quantize_rotations: true,
+            min_weight: 0.01,
+            optimize_mesh: false,
+            lod_ratios: Vec::new(),
+            lod_distances: Vec::new()
+        }
+    }
+}
+impl Config {
+    /// Settings of an asset under `root`, reading every `compile.conf` on the way like the directory walk.
+    pub fn asset(root: impl AsRef<Path>, path: impl AsRef<Path>) -> Result<Self, String> {
+        let (root, path) = (root.as_ref(), path.as_ref());
+        let relative = path.parent().and_then(|v| v.strip_prefix(root).ok())
+            .ok_or_else(|| format!("{} is not in {}", path.display(), root.display()))?;
+        let mut dir = root.to_path_buf();
+        let mut res = Self::default().directory(&dir)?;
+        for component in relative.components() {
+            dir.push(component);
+            res = res.directory(&dir)?;
+        }
+        res.file(path)
+    }
+    /// Settings of a directory, `self` being the settings of its parent.
+    pub fn directory(&self, path: impl AsRef<Path>) -> Result<Self, String> {
+        self.read(path.as_ref().join("compile.conf"))
+    }
+    /// Settings of an asset, `self` being the settings of its directory.
+    pub fn file(&self, path: impl AsRef<Path>) -> Result<Self, String> {
+        let mut name = path.as_ref().file_name().unwrap().to_os_string();
+        name.push(".conf");
+        self.read(path.as_ref().with_file_name(name))
+    }
+    fn read(&self, path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let data = match fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(self.clone()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e))
+        };
+        let mut res = self.clone();
+        for (line_id, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue }
+            res.set(line).map_err(|e| format!("{}:{}: {}", path.display(), line_id + 1, e))?;
+        }
+        Ok(res)
+    }
+    fn set(&mut self, line: &str) -> Result<(), String> {
+        let (key, value) = line.split_once('=').ok_or_else(|| format!("Expected Key=Value, found: {}", line))?;
+        let (key, value) = (key.trim(), value.trim());
+        match key {
+            "VertexType" => self.vertex_type = match value {
+                "Basic" => VertexType::Basic,
+                "N" => VertexType::N,
+                "NJW" => VertexType::NJW,
+                "NT" => VertexType::NT,
+                "NTJW" => VertexType::NTJW,
+                _ => return Err(invalid(key, value, "Basic, N, NJW, NT or NTJW"))
+            },
+            "Scale" => self.scale = parse(key, value, |v: &f32| *v > 0., "a number greater than 0")?,
+            "UpAxis" => self.up_axis = match value {
+                "Y" => UpAxis::Y,
+                "Z" => UpAxis::Z,
+                _ => return Err(invalid(key, value, "Y or Z"))
+            },
+            "JointReplace" => match value.split_once("->") {
+                _ if value.is_empty() => self.joint_replace.clear(),
+                Some((from, to)) if !from.trim().is_empty() => self.joint_replace.push((from.trim().to_string(), to.trim().to_string())),
+                _ => return Err(invalid(key, value, "<from> -> <to>"))
+            },
+            "JointCase" => self.joint_case = match value {
+                "Keep" => JointCase::Keep,
+                "Lower" => JointCase::Lower,
+                "Upper" => JointCase::Upper,
+                _ => return Err(invalid(key, value, "Keep, Lower or Upper"))
+            },
+            "TextureKind" => self.texture_kind = match value {
+                "Color" => TextureKind::Color,
+                "Linear" => TextureKind::Linear,
+                "Normal" => TextureKind::Normal,
+                "Mask" => TextureKind::Mask,
+                _ => return Err(invalid(key, value, "Color, Linear, Normal or Mask"))
+            },
+            "TextureCompression" => self.texture_compression = match value {
+                "None" => TextureCompression::None,
+                "BC" => TextureCompression::Bc,
+                _ => return Err(invalid(key, value, "None or BC"))
+            },
+            "Mipmaps" => self.mipmaps = parse(key, value, |_| true, "true or false")?,
+            "SampleRate" => self.sample_rate = parse(key, value, |v: &f32| *v > 0., "a number greater than 0")?,
+            "AnimationTolerance" => self.animation_tolerance = parse(key, value, |v: &f32| *v >= 0., "a number greater or equal to 0")?,
+            "QuantizeRotations" => self.quantize_rotations = parse(key, value, |_| true, "true or false")?,
+            "MinWeight" => self.min_weight = parse(key, value, |v: &f32| (0. ..1.).contains(v), "a number from 0 to less than 1")?,
+            "OptimizeMesh" => self.optimize_mesh = parse(key, value, |_| true, "true or false")?,
+            "LodRatios" => self.lod_ratios = parse_list(key, value, |v| v.windows(2).all(|v| v[1] < v[0]) && v.iter().all(|v| *v > 0. && *v < 1.),
+                "decreasing numbers between 0 and 1")?,
+            "LodDistances" => self.lod_distances = parse_list(key, value, |v| v.windows(2).all(|v| v[1] > v[0]) && v.iter().all(|v| *v >= 0.),
+                "increasing numbers greater or equal to 0")?,
+            _ => return Err(format!("Unknown key: {}", key))
+        }
+        Ok(())
+    }
+    /// Rotation from the source up axis to Y-up.
+    pub fn axis_rotation(&self) -> Quaternion<f32> {
+        match self.up_axis {
+            UpAxis::Y => Quaternion::one(),
+            UpAxis::Z => Quaternion::from_angle_x(Deg(-90.))
+        }
+    }
+    /// Scale and axis conversion from the source asset to the engine space.
+    pub fn conversion(&self) -> Matrix4<f32> {
+        Matrix4::from_scale(self.scale) * Matrix4::from(self.axis_rotation())
+    }
+    /// Converts a local translation, rotation or scale like `conversion() * local * conversion().invert()`.
+    pub fn convert(&self, property: Property, v: [f32;4]) -> [f32;4] {
+        let axis = self.axis_rotation();
+        let v3 = Vector3::new(v[0], v[1], v[2]);
+        match property {
+            Property::Translation => (axis.rotate_vector(v3) * self.scale).extend(0.).into(),
+            Property::Rotation => quaternion_to_array(axis * array_to_quaternion(v) * axis.conjugate()),
+            Property::Scale => {
+                // The axis rotation only swaps axes, scales are swapped the same way
+                let m = Matrix3::from(axis);
+                (Matrix3::from_cols(m.x.map(f32::abs), m.y.map(f32::abs), m.z.map(f32::abs)) * v3).extend(0.).into()
+            }
+        }
+    }
+    pub fn convert_trs(&self, mut trs: Trs) -> Trs {
+        for property in [Property::Translation, Property::Rotation, Property::Scale] {
+            trs.set(property, self.convert(property, trs.get(property)));
+        }
+        trs
+    }
+    pub fn rename_joint(&self, name: &str) -> String {
+        let mut res = name.to_string();
+        for (from, to) in &self.joint_replace {
+            res = res.replace(from, to);
+        }
+        match self.joint_case {
+            JointCase::Keep => res,
+            JointCase::Lower => res.to_lowercase(),
+            JointCase::Upper => res.to_uppercase()
+        }
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str, valid: fn(&T) -> bool, expected: &str) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(v) if valid(&v) => Ok(v),
+        _ => Err(invalid(key, value, expected))
+    }
+}
+/// Comma separated values, an empty value is an empty list.
+fn parse_list(key: &str, value: &str, valid: fn(&[f32]) -> bool, expected: &str) -> Result<Vec<f32>, String> {
+    match value.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()).map(str::parse).collect::<Result<Vec<f32>, _>>() {
+        Ok(v) if valid(&v) => Ok(v),
+        _ => Err(invalid(key, value, expected))
+    }
+}
+fn invalid(key: &str, value: &str, expected: &str) -> String {
+    format!("Invalid {}: {}, expected {}", key, value, expected)
+}
@@ -0,0 +1,489 @@
+use std::{fs, ops::Range, path::Path};
+use cgmath::Matrix4;
+
+use crate::{bc, keyframes::{Interpolation, Property, Track, Weights}, low::{self, Kind, Section, TextureCompression, TextureKind}, material::{AlphaMode, MaterialInfo}, nodes::{Camera, Light, Node}};
+
+/// Prints the content of any compiled `.low` file, `export` writes meshes and animations back
+/// to glTF and textures to PNG for external viewers.
+pub fn run(path: &Path, export: Option<&Path>) -> Result<(), low::Error> {
+    let file = low::File::parse(fs::read(path)?)?;
+    println!("File: {}, version {}, kind {:?}", path.display(), file.version, file.kind);
+    for (id, length) in file.section_lengths() {
+        match Section::from_id(id) {
+            Some(section) => println!("    {:?}: {} bytes", section, length),
+            None => println!("    Unknown section {}: {} bytes", id, length)
+        }
+    }
+    for (key, value) in file.metadata() {
+        println!("    {}: {}", key, value);
+    }
+    match file.kind {
+        Kind::Mesh => mesh(&file, export),
+        Kind::Image => texture(&file, export),
+        Kind::Animation => animation(&file, export),
+        Kind::Scene => match export {
+            Some(_) => Err(low::Error::Invalid("Scenes can not be exported, export their mesh".into())),
+            None => scene(&file)
+        }
+    }
+}
+
+/// Attributes of every vertex type in file order with their component count, joints are u16.
+fn attributes(vertex_type: &str) -> Option<&'static [(&'static str, usize)]> {
+    Some(match vertex_type {
+        "Basic" => &[("position", 3)],
+        "N" => &[("position", 3), ("normal", 3), ("uv", 2)],
+        "NJW" => &[("position", 3), ("normal", 3), ("uv", 2), ("joints", 4), ("weights", 4)],
+        "NT" => &[("position", 3), ("normal", 3), ("tangent", 4), ("uv", 2)],
+        "NTJW" => &[("position", 3), ("normal", 3), ("tangent", 4), ("uv", 2), ("joints", 4), ("weights", 4)],
+        _ => return None
+    })
+}
+
+struct Target {
+    mesh: String,
+    name: String,
+    weight: f32,
+    /// Position then normal deltas of every vertex.
+    deltas: [Vec<[f32;3]>;2]
+}
+
+struct Joint {
+    name: String,
+    parent: Option<usize>,
+    tpose: [[f32;4];4],
+    ibm: [[f32;4];4]
+}
+
+struct Submesh {
+    name: String,
+    material: usize,
+    indices: Range<u32>,
+    vertices: Range<u32>
+}
+
+fn mesh(file: &low::File, export: Option<&Path>) -> Result<(), low::Error> {
+    let mut cursor = file.require(Section::Vertices)?;
+    let vertex_type = cursor.read_str();
+    let layout = attributes(&vertex_type).ok_or_else(|| low::Error::Invalid(format!("Invalid VertexType: {}", vertex_type)))?;
+    let vertices = cursor.read_u32() as usize;
+    if vertices > cursor.remaining() { return Err(low::Error::Truncated(Section::Vertices)) }
+    let mut values = vec![Vec::with_capacity(vertices); layout.len()];
+    for _ in 0..vertices {
+        for ((name, components), values) in layout.iter().zip(values.iter_mut()) {
+            let mut v = [0.;4];
+            for c in v.iter_mut().take(*components) {
+                *c = if *name == "joints" { cursor.read_u16() as f32 } else { cursor.read_f32() };
+            }
+            values.push(v);
+        }
+    }
+    if cursor.overflowed() || cursor.remaining() > 0 { return Err(low::Error::Truncated(Section::Vertices)) }
+    let stride: usize = layout.iter().map(|(name, components)| if *name == "joints" { components * 2 } else { components * 4 }).sum();
+    println!("Vertices: {}, {} vertices, {} bytes each", vertex_type, vertices, stride);
+    for ((name, components), values) in layout.iter().zip(&values) {
+        let (mut min, mut max) = ([f32::MAX;4], [f32::MIN;4]);
+        for v in values {
+            for c in 0..*components {
+                min[c] = min[c].min(v[c]);
+                max[c] = max[c].max(v[c]);
+            }
+        }
+        let invalid = values.iter().filter(|v| v.iter().any(|c| !c.is_finite())).count();
+        if values.is_empty() {
+            println!("    {}: empty", name);
+        } else {
+            println!("    {}: min {} max {}{}", name, vector(&min[..*components]), vector(&max[..*components]),
+                if invalid > 0 { format!(", {} not finite", invalid) } else { String::new() });
+        }
+        if *name == "weights" && !values.is_empty() {
+            let sums: Vec<f32> = values.iter().map(|v| v.iter().sum()).collect();
+            println!("    weight sums: min {:.4} max {:.4}", sums.iter().fold(f32::MAX, |a, b| a.min(*b)), sums.iter().fold(f32::MIN, |a, b| a.max(*b)));
+        }
+    }
+
+    let mut cursor = file.require(Section::Indices)?;
+    let index_size = cursor.read_u8();
+    let indices_length = cursor.read_u32() as usize;
+    if indices_length > cursor.remaining() { return Err(low::Error::Truncated(Section::Indices)) }
+    let indices: Vec<u32> = match index_size {
+        2 => (0..indices_length).map(|_| cursor.read_u16() as u32).collect(),
+        4 => (0..indices_length).map(|_| cursor.read_u32()).collect(),
+        _ => return Err(low::Error::Invalid(format!("Invalid index size: {}", index_size)))
+    };
+    if cursor.overflowed() { return Err(low::Error::Truncated(Section::Indices)) }
+    println!("Indices: {}, {} bytes each, largest {}{}", indices.len(), index_size, indices.iter().max().map_or("none".into(), |v| v.to_string()),
+        if indices.iter().any(|v| *v as usize >= vertices) { ", out of range" } else { "" });
+
+    let mut cursor = file.require(Section::Materials)?;
+    let materials = (0..cursor.read_u16()).map(|_| MaterialInfo::read(&mut cursor)).collect::<Result<Vec<_>, _>>()?;
+    println!("Materials: {}", materials.len());
+    for (slot, material) in materials.iter().enumerate() {
+        println!("    {} {}: base color {}, {:?}, metallic {}, roughness {}", slot, material.name, vector(&material.base_color), material.alpha_mode, material.metallic, material.roughness);
+        for (name, texture) in [
+            ("base color", &material.base_color_texture), ("metallic roughness", &material.metallic_roughness_texture),
+            ("normal", &material.normal_texture), ("occlusion", &material.occlusion_texture), ("emissive", &material.emissive_texture)
+        ] {
+            if let Some(texture) = texture {
+                println!("        {}: {}", name, texture);
+            }
+        }
+    }
+
+    let mut cursor = file.require(Section::Submeshes)?;
+    let mut submeshes = Vec::new();
+    for _ in 0..cursor.read_u16() {
+        let (name, material) = (cursor.read_str(), cursor.read_u16() as usize);
+        let (first_index, indices_len, first_vertex, vertices_len) = (cursor.read_u32(), cursor.read_u32(), cursor.read_u32(), cursor.read_u32());
+        submeshes.push(Submesh { name, material, indices: first_index..first_index.saturating_add(indices_len), vertices: first_vertex..first_vertex.saturating_add(vertices_len) });
+    }
+    if cursor.overflowed() { return Err(low::Error::Truncated(Section::Submeshes)) }
+    println!("Submeshes: {}", submeshes.len());
+    for submesh in &submeshes {
+        println!("    {}: material {}, indices {:?}, vertices {:?}", submesh.name, submesh.material, submesh.indices, submesh.vertices);
+        if submesh.material >= materials.len() || submesh.indices.end as usize > indices.len() || submesh.vertices.end as usize > vertices {
+            return Err(low::Error::Invalid(format!("Submesh {} is out of range", submesh.name)))
+        }
+    }
+
+    if let Some(mut cursor) = file.section(Section::Lods) {
+        let (center, radius) = (cursor.read_vec3(), cursor.read_f32());
+        println!("Bounds: center {}, radius {:.4}", vector(&center), radius);
+        for level in 0..cursor.read_u8() {
+            let distance = cursor.read_f32();
+            let triangles: u32 = submeshes.iter().map(|_| {
+                cursor.read_u32();
+                cursor.read_u32() / 3
+            }).sum();
+            println!("    Lod {}: from {}, {} triangles", level + 1, distance, triangles);
+        }
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Lods)) }
+    }
+
+    let mut joints = Vec::new();
+    if let Some(mut cursor) = file.section(Section::Skeleton) {
+        for _ in 0..cursor.read_u16() {
+            let name = cursor.read_str();
+            let parent = Some(cursor.read_u16()).filter(|v| *v != u16::MAX).map(|v| v as usize);
+            joints.push(Joint { name, parent, tpose: cursor.read_mat4x4(), ibm: cursor.read_mat4x4() });
+        }
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Skeleton)) }
+        let parents: Vec<Option<usize>> = joints.iter().map(|v| v.parent).collect();
+        if let Some(id) = low::invalid_parent(&parents) {
+            return Err(low::Error::Invalid(format!("Joint {} has an invalid parent or a cycle in its parents", joints[id].name)))
+        }
+        println!("Skeleton: {} joints", joints.len());
+        tree(&parents, |id, depth| println!("    {}{} {}{}", "    ".repeat(depth), id, joints[id].name,
+            joints[id].parent.map_or(String::new(), |p| format!(", parent {} {}", p, joints[p].name))));
+        println!("Joint matrices, columns of the bind pose and the inverse bind matrix:");
+        for joint in &joints {
+            println!("    {}:\n        {}\n        {}", joint.name, matrix(&joint.tpose), matrix(&joint.ibm));
+        }
+    }
+
+    let mut targets: Vec<Target> = Vec::new();
+    if let Some(mut cursor) = file.section(Section::Targets) {
+        for _ in 0..cursor.read_u16() {
+            let (mesh, name) = (cursor.read_str(), cursor.read_str());
+            targets.push(Target { mesh, name, weight: cursor.read_f32(), deltas: [Vec::new(), Vec::new()] });
+        }
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Targets)) }
+        for (section, k) in [(Section::PositionDeltas, 0), (Section::NormalDeltas, 1)] {
+            let mut cursor = file.require(section)?;
+            if cursor.remaining() != targets.len() * vertices * 12 { return Err(low::Error::Truncated(section)) }
+            for target in &mut targets {
+                target.deltas[k] = (0..vertices).map(|_| cursor.read_vec3()).collect();
+            }
+        }
+        println!("Morph targets: {}", targets.len());
+        let length = |v: &[f32;3]| (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        for Target { mesh, name, weight, deltas } in &targets {
+            println!("    {}/{}: weight {}, {} vertices moved, position deltas up to {:.4}, normal deltas up to {:.4}", mesh, name, weight,
+                deltas[0].iter().filter(|v| length(v) > 0.).count(), deltas[0].iter().map(length).fold(0., f32::max), deltas[1].iter().map(length).fold(0., f32::max));
+        }
+    }
+
+    if let Some(path) = export {
+        let mut gltf = Gltf::default();
+        let mut attributes = Vec::new();
+        for ((name, components), values) in layout.iter().zip(&values) {
+            let (semantic, kind) = match (*name, components) {
+                ("position", _) => ("POSITION", "VEC3"),
+                ("normal", _) => ("NORMAL", "VEC3"),
+                ("tangent", _) => ("TANGENT", "VEC4"),
+                ("uv", _) => ("TEXCOORD_0", "VEC2"),
+                ("joints", _) => ("JOINTS_0", "VEC4"),
+                _ => ("WEIGHTS_0", "VEC4")
+            };
+            let accessor = match *name {
+                "joints" => gltf.accessor(values.iter().flat_map(|v| v.map(|c| c as u16)).flat_map(u16::to_le_bytes).collect(), 5123, values.len(), kind, ""),
+                "position" => {
+                    let bounds = |f: fn(f32, f32) -> f32, start| numbers(&(0..3).map(|c| values.iter().map(|v| v[c]).fold(start, f)).collect::<Vec<_>>());
+                    let extra = format!(",\"min\":{},\"max\":{}", bounds(f32::min, f32::MAX), bounds(f32::max, f32::MIN));
+                    gltf.accessor(floats(values.iter().flat_map(|v| v[..*components].to_vec())), 5126, values.len(), kind, &extra)
+                }
+                _ => gltf.accessor(floats(values.iter().flat_map(|v| v[..*components].to_vec())), 5126, values.len(), kind, "")
+            };
+            attributes.push(format!("\"{}\":{}", semantic, accessor));
+        }
+        // Position accessors of morph targets need their bounds too
+        let mut morph_targets = Vec::new();
+        for Target { deltas, .. } in &targets {
+            let bounds = |f: fn(f32, f32) -> f32, start| numbers(&(0..3).map(|c| deltas[0].iter().map(|v| v[c]).fold(start, f)).collect::<Vec<_>>());
+            let extra = format!(",\"min\":{},\"max\":{}", bounds(f32::min, f32::MAX), bounds(f32::max, f32::MIN));
+            let position = gltf.accessor(floats(deltas[0].iter().flatten().copied()), 5126, vertices, "VEC3", &extra);
+            let normal = gltf.accessor(floats(deltas[1].iter().flatten().copied()), 5126, vertices, "VEC3", "");
+            morph_targets.push(format!("{{\"POSITION\":{},\"NORMAL\":{}}}", position, normal));
+        }
+        let morph_targets = match morph_targets.is_empty() {
+            true => String::new(),
+            false => format!(",\"targets\":[{}]", morph_targets.join(","))
+        };
+        let primitives: Vec<String> = submeshes.iter().map(|submesh| {
+            let range = &indices[submesh.indices.start as usize..submesh.indices.end as usize];
+            let accessor = gltf.accessor(range.iter().flat_map(|v| v.to_le_bytes()).collect(), 5125, range.len(), "SCALAR", "");
+            format!("{{\"attributes\":{{{}}},\"indices\":{},\"material\":{}{}}}", attributes.join(","), accessor, submesh.material, morph_targets)
+        }).collect();
+        let materials: Vec<String> = materials.iter().map(|v| {
+            let alpha = match v.alpha_mode {
+                AlphaMode::Opaque => "\"OPAQUE\"".to_string(),
+                AlphaMode::Mask => format!("\"MASK\",\"alphaCutoff\":{}", number(v.alpha_cutoff)),
+                AlphaMode::Blend => "\"BLEND\"".to_string()
+            };
+            format!("{{\"name\":{},\"pbrMetallicRoughness\":{{\"baseColorFactor\":{},\"metallicFactor\":{},\"roughnessFactor\":{}}},\"emissiveFactor\":{},\"alphaMode\":{}}}",
+                string(&v.name), numbers(&v.base_color), number(v.metallic), number(v.roughness), numbers(&v.emissive), alpha)
+        }).collect();
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut nodes = vec![format!("{{\"name\":{},\"mesh\":0{}}}", string(&name), if joints.is_empty() { "" } else { ",\"skin\":0" })];
+        let mut roots = vec![0];
+        for (id, joint) in joints.iter().enumerate() {
+            let local = match joint.parent {
+                Some(parent) => Matrix4::from(joints[parent].ibm) * Matrix4::from(joint.tpose),
+                None => Matrix4::from(joint.tpose)
+            };
+            let children: Vec<String> = joints.iter().enumerate().filter(|(_, v)| v.parent == Some(id)).map(|(v, _)| (v + 1).to_string()).collect();
+            let local: [[f32;4];4] = local.into();
+            nodes.push(format!("{{\"name\":{},\"matrix\":{},\"children\":[{}]}}", string(&joint.name), numbers(local.as_flattened()), children.join(",")));
+            if joint.parent.is_none() { roots.push(id + 1) }
+        }
+        let morph = match targets.is_empty() {
+            true => String::new(),
+            false => format!(",\"weights\":{},\"extras\":{{\"targetNames\":[{}]}}", numbers(&targets.iter().map(|v| v.weight).collect::<Vec<_>>()),
+                targets.iter().map(|v| string(&v.name)).collect::<Vec<_>>().join(","))
+        };
+        let mut body = format!("\"scene\":0,\"scenes\":[{{\"nodes\":{:?}}}],\"nodes\":[{}],\"meshes\":[{{\"name\":{},\"primitives\":[{}]{}}}],\"materials\":[{}]",
+            roots, nodes.join(","), string(&name), primitives.join(","), morph, materials.join(","));
+        if !joints.is_empty() {
+            let ibms = gltf.accessor(floats(joints.iter().flat_map(|v| v.ibm.as_flattened().to_vec())), 5126, joints.len(), "MAT4", "");
+            body += &format!(",\"skins\":[{{\"inverseBindMatrices\":{},\"joints\":{:?}}}]", ibms, (1..=joints.len()).collect::<Vec<_>>());
+        }
+        gltf.save(path, &body)?;
+    }
+    Ok(())
+}
+
+fn texture(file: &low::File, export: Option<&Path>) -> Result<(), low::Error> {
+    let mut cursor = file.require(Section::Pixels)?;
+    let (width, height) = (cursor.read_u32(), cursor.read_u32());
+    let kind_id = cursor.read_u8();
+    let kind = TextureKind::from_id(kind_id).ok_or_else(|| low::Error::Invalid(format!("Invalid texture kind: {}", kind_id)))?;
+    let compression_id = cursor.read_u8();
+    let compression = TextureCompression::from_id(compression_id).ok_or_else(|| low::Error::Invalid(format!("Invalid texture compression: {}", compression_id)))?;
+    let levels = cursor.read_u8() as u32;
+    println!("Texture: {}x{}, {:?}, {:?} compression, {} mip levels", width, height, kind, compression, levels);
+    let mut pixels = Vec::new();
+    for level in 0..levels {
+        let (w, h) = ((width >> level).max(1), (height >> level).max(1));
+        let length = match compression {
+            TextureCompression::None => (w * h) as usize * kind.channels(),
+            TextureCompression::Bc => (w.div_ceil(4) * h.div_ceil(4)) as usize * bc::block_size(kind)
+        };
+        if length > cursor.remaining() { return Err(low::Error::Truncated(Section::Pixels)) }
+        let data = cursor.read_bytes(length);
+        println!("    {}: {}x{}, {} bytes", level, w, h, length);
+        if level == 0 { pixels = data }
+    }
+    if cursor.remaining() > 0 { println!("    {} bytes after the last level", cursor.remaining()) }
+
+    if let Some(path) = export {
+        if levels == 0 { return Err(low::Error::Invalid("Texture has no levels to export".into())) }
+        if compression == TextureCompression::Bc {
+            pixels = bc::decode(kind, width, height, &pixels).map_err(low::Error::Invalid)?;
+        }
+        let (pixels, color) = match kind {
+            TextureKind::Color | TextureKind::Linear => (pixels, image::ColorType::Rgba8),
+            // z is reconstructed like the shaders do
+            TextureKind::Normal => (pixels.chunks(2).flat_map(|v| {
+                let (x, y) = (v[0] as f32 / 255. * 2. - 1., v[1] as f32 / 255. * 2. - 1.);
+                let z = (1. - x * x - y * y).max(0.).sqrt();
+                [v[0], v[1], ((z * 0.5 + 0.5) * 255.).round() as u8]
+            }).collect(), image::ColorType::Rgb8),
+            TextureKind::Mask => (pixels, image::ColorType::L8)
+        };
+        image::save_buffer(path, &pixels, width, height, color).map_err(|e| low::Error::Invalid(e.to_string()))?;
+        println!("Exported: {}", path.display());
+    }
+    Ok(())
+}
+
+fn animation(file: &low::File, export: Option<&Path>) -> Result<(), low::Error> {
+    let mut cursor = file.require(Section::Channels)?;
+    let duration = cursor.read_f32();
+    let tracks = (0..cursor.read_u16()).map(|_| Track::read(&mut cursor)).collect::<Result<Vec<_>, _>>()?;
+    let channels: Vec<_> = tracks.iter().flat_map(|v| v.channels.iter()).collect();
+    println!("Clip: {:.3}s, {} joints, {} channels, {} keys", duration, tracks.len(), channels.len(), channels.iter().map(|v| v.times.len()).sum::<usize>());
+    for track in &tracks {
+        println!("    {}: rest translation {}, rotation {}, scale {}", track.name, vector(&track.rest.translation), vector(&track.rest.rotation), vector(&track.rest.scale));
+        for channel in &track.channels {
+            println!("        {:?}, {:?}, {:?}, {} keys, {:.3}s to {:.3}s", channel.property, channel.interpolation, channel.encoding,
+                channel.times.len(), channel.times[0], channel.duration());
+        }
+    }
+    if let Some(mut cursor) = file.section(Section::Weights) {
+        let weights = (0..cursor.read_u16()).map(|_| Weights::read(&mut cursor)).collect::<Result<Vec<_>, _>>()?;
+        println!("Weights: {} meshes", weights.len());
+        for track in &weights {
+            println!("    {}: {:?}, {} targets, {} keys, {:.3}s to {:.3}s", track.mesh, track.interpolation, track.targets,
+                track.times.len(), track.times[0], track.duration());
+        }
+        if export.is_some() { println!("Weights are not exported, they need the mesh they animate") }
+    }
+
+    if let Some(path) = export {
+        let mut gltf = Gltf::default();
+        let nodes: Vec<String> = tracks.iter().map(|v| format!("{{\"name\":{},\"translation\":{},\"rotation\":{},\"scale\":{}}}",
+            string(&v.name), numbers(&v.rest.translation), numbers(&v.rest.rotation), numbers(&v.rest.scale))).collect();
+        let (mut samplers, mut targets) = (Vec::new(), Vec::new());
+        for (node, track) in tracks.iter().enumerate() {
+            for channel in &track.channels {
+                let components = channel.property.components();
+                let input = gltf.accessor(floats(channel.times.iter().copied()), 5126, channel.times.len(), "SCALAR",
+                    &format!(",\"min\":[{}],\"max\":[{}]", number(channel.times[0]), number(channel.duration())));
+                let output = gltf.accessor(floats(channel.values.iter().flat_map(|v| v[..components].to_vec())), 5126, channel.values.len(),
+                    if components == 4 { "VEC4" } else { "VEC3" }, "");
+                let interpolation = match channel.interpolation {
+                    Interpolation::Step => "STEP",
+                    Interpolation::Linear => "LINEAR",
+                    Interpolation::CubicSpline => "CUBICSPLINE"
+                };
+                let property = match channel.property {
+                    Property::Translation => "translation",
+                    Property::Rotation => "rotation",
+                    Property::Scale => "scale"
+                };
+                targets.push(format!("{{\"sampler\":{},\"target\":{{\"node\":{},\"path\":\"{}\"}}}}", samplers.len(), node, property));
+                samplers.push(format!("{{\"input\":{},\"output\":{},\"interpolation\":\"{}\"}}", input, output, interpolation));
+            }
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let body = format!("\"scene\":0,\"scenes\":[{{\"nodes\":{:?}}}],\"nodes\":[{}],\"animations\":[{{\"name\":{},\"samplers\":[{}],\"channels\":[{}]}}]",
+            (0..tracks.len()).collect::<Vec<_>>(), nodes.join(","), string(&name), samplers.join(","), targets.join(","));
+        gltf.save(path, &body)?;
+    }
+    Ok(())
+}
+
+fn scene(file: &low::File) -> Result<(), low::Error> {
+    let mut cursor = file.require(Section::Nodes)?;
+    let mesh_path = cursor.read_str();
+    let nodes = (0..cursor.read_u16()).map(|_| Node::read(&mut cursor)).collect::<Result<Vec<_>, _>>()?;
+    let mut cursor = file.require(Section::Lights)?;
+    let lights = (0..cursor.read_u16()).map(|_| Light::read(&mut cursor)).collect::<Result<Vec<_>, _>>()?;
+    let mut cursor = file.require(Section::Cameras)?;
+    let cameras = (0..cursor.read_u16()).map(|_| Camera::read(&mut cursor)).collect::<Result<Vec<_>, _>>()?;
+    println!("Scene: mesh {}, {} nodes, {} lights, {} cameras", mesh_path, nodes.len(), lights.len(), cameras.len());
+    if let Some(node) = nodes.iter().enumerate().find(|(id, v)| v.parent.is_some_and(|p| p >= *id)).map(|v| v.1) {
+        return Err(low::Error::Invalid(format!("Node {} is stored before its parent", node.name)))
+    }
+    let parents: Vec<Option<usize>> = nodes.iter().map(|v| v.parent).collect();
+    tree(&parents, |id, depth| {
+        let node = &nodes[id];
+        let mut line = format!("    {}{} {}: translation {}, rotation {}, scale {}", "    ".repeat(depth), id, node.name,
+            vector(&node.local.translation), vector(&node.local.rotation), vector(&node.local.scale));
+        if !node.submeshes.is_empty() { line += &format!(", submeshes {:?}", node.submeshes) }
+        for (name, index) in [("skin", node.skin), ("camera", node.camera), ("light", node.light)] {
+            if let Some(index) = index { line += &format!(", {} {}", name, index) }
+        }
+        println!("{}", line);
+    });
+    for (id, light) in lights.iter().enumerate() {
+        println!("    Light {}: {:?}", id, light);
+    }
+    for (id, camera) in cameras.iter().enumerate() {
+        println!("    Camera {}: {:?}", id, camera);
+    }
+    Ok(())
+}
+
+/// Visits every item depth first from the roots, children in index order.
+fn tree(parents: &[Option<usize>], mut f: impl FnMut(usize, usize)) {
+    let mut stack: Vec<(usize, usize)> = parents.iter().enumerate().rev().filter(|(_, v)| v.is_none()).map(|(id, _)| (id, 0)).collect();
+    while let Some((id, depth)) = stack.pop() {
+        f(id, depth);
+        stack.extend(parents.iter().enumerate().rev().filter(|(_, v)| **v == Some(id)).map(|(child, _)| (child, depth + 1)));
+    }
+}
+
+fn vector(v: &[f32]) -> String {
+    format!("({})", v.iter().map(|v| format!("{:.4}", v)).collect::<Vec<_>>().join(", "))
+}
+
+fn matrix(m: &[[f32;4];4]) -> String {
+    m.iter().map(|v| vector(v)).collect::<Vec<_>>().join(" ")
+}
+
+/// Buffer views and accessors of an exported glTF, the binary buffer is saved next to the JSON.
+#[derive(Default)]
+struct Gltf {
+    buffer: Vec<u8>,
+    views: Vec<String>,
+    accessors: Vec<String>
+}
+impl Gltf {
+    /// Adds `bytes` as its own buffer view and returns the index of the accessor reading it.
+    fn accessor(&mut self, bytes: Vec<u8>, component_type: u32, count: usize, kind: &str, extra: &str) -> usize {
+        self.views.push(format!("{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{}}}", self.buffer.len(), bytes.len()));
+        self.accessors.push(format!("{{\"bufferView\":{},\"componentType\":{},\"count\":{},\"type\":\"{}\"{}}}",
+            self.views.len() - 1, component_type, count, kind, extra));
+        self.buffer.extend_from_slice(&bytes);
+        // Views start 4 byte aligned
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+        self.accessors.len() - 1
+    }
+    fn save(&self, path: &Path, body: &str) -> Result<(), low::Error> {
+        let bin = path.with_extension("bin");
+        let json = format!("{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"compile --inspect\"}},\"buffers\":[{{\"uri\":{},\"byteLength\":{}}}],\"bufferViews\":[{}],\"accessors\":[{}],{}}}",
+            string(&bin.file_name().unwrap_or_default().to_string_lossy()), self.buffer.len(), self.views.join(","), self.accessors.join(","), body);
+        fs::write(&bin, &self.buffer)?;
+        fs::write(path, json)?;
+        println!("Exported: {}, {}", path.display(), bin.display());
+        Ok(())
+    }
+}
+
+/// Little endian floats, glTF buffers are little endian.
+fn floats(values: impl Iterator<Item = f32>) -> Vec<u8> {
+    values.flat_map(f32::to_le_bytes).collect()
+}
+
+/// JSON has no infinity or NaN, they are written as 0.
+fn number(v: f32) -> String {
+    if v.is_finite() { v.to_string() } else { "0".into() }
+}
+
+fn numbers(v: &[f32]) -> String {
+    format!("[{}]", v.iter().map(|v| number(*v)).collect::<Vec<_>>().join(","))
+}
+
+fn string(v: &str) -> String {
+    let mut res = String::from("\"");
+    for c in v.chars() {
+        match c {
+            '"' => res += "\\\"",
+            '\\' => res += "\\\\",
+            c if (c as u32) < 0x20 => res += &format!("\\u{:04x}", c as u32),
+            c => res.push(c)
+        }
+    }
+    res + "\""
+}
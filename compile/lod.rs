@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use cgmath::{InnerSpace, Vector3};
+
+/// Squared distance to a set of planes weighted by their area, a symmetric 4x4 matrix.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64;10]);
+impl Quadric {
+    fn plane(normal: Vector3<f64>, d: f64, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        Self([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|v| v * weight))
+    }
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+    }
+    fn error(&self, p: Vector3<f64>) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x + 2. * q[1] * x * y + 2. * q[2] * x * z + 2. * q[3] * x
+            + q[4] * y * y + 2. * q[5] * y * z + 2. * q[6] * y
+            + q[7] * z * z + 2. * q[8] * z
+            + q[9]
+    }
+}
+
+/// Simplifies a triangle list down to about `target` indices by collapsing edges onto one of their
+/// vertices in order of quadric error, so the simplified triangles reuse the existing vertices.
+/// Vertices split by a UV or normal seam and vertices on open borders are kept, collapses only
+/// happen between vertices influenced most by the same joint of `joints` and never turn a triangle
+/// by more than about 75 degrees.
+pub fn simplify(indices: &[u32], positions: &[[f32;3]], joints: Option<&[u16]>, target: usize) -> Vec<u32> {
+    let vertices = positions.len();
+    let position = |v: u32| Vector3::from(positions[v as usize]).cast::<f64>().unwrap();
+    // Vertices sharing a position are the same point of the surface
+    let mut groups: HashMap<[u32;3], usize> = HashMap::new();
+    let group: Vec<usize> = positions.iter().map(|p| {
+        let next = groups.len();
+        *groups.entry(p.map(f32::to_bits)).or_insert(next)
+    }).collect();
+    let mut group_size = vec![0; groups.len()];
+    for g in &group {
+        group_size[*g] += 1;
+    }
+    let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+    for t in indices.chunks(3) {
+        for k in 0..3 {
+            let (a, b) = (group[t[k] as usize], group[t[(k + 1) % 3] as usize]);
+            *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+    let mut border = vec![false; groups.len()];
+    for ((a, b), count) in edges {
+        if count == 1 {
+            border[a] = true;
+            border[b] = true;
+        }
+    }
+    let locked: Vec<bool> = group.iter().map(|g| group_size[*g] > 1 || border[*g]).collect();
+
+    let mut quadrics = vec![Quadric::default(); vertices];
+    for t in indices.chunks(3) {
+        let (a, b, c) = (position(t[0]), position(t[1]), position(t[2]));
+        let cross = (b - a).cross(c - a);
+        let area = cross.magnitude() * 0.5;
+        if area == 0. { continue }
+        let normal = cross.normalize();
+        let quadric = Quadric::plane(normal, -normal.dot(a), area);
+        for v in t {
+            quadrics[*v as usize].add(&quadric);
+        }
+    }
+
+    let mut res = indices.to_vec();
+    while res.len() > target {
+        let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); vertices];
+        for (id, t) in res.chunks(3).enumerate() {
+            for v in t {
+                adjacency[*v as usize].push(id);
+            }
+        }
+        let mut candidates = Vec::new();
+        for t in res.chunks(3) {
+            for k in 0..3 {
+                let (a, b) = (t[k], t[(k + 1) % 3]);
+                for (from, to) in [(a, b), (b, a)] {
+                    if locked[from as usize] || joints.is_some_and(|j| j[from as usize] != j[to as usize]) { continue }
+                    candidates.push((quadrics[from as usize].error(position(to)), from, to));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut touched = vec![false; vertices];
+        let mut remap: Vec<u32> = (0..vertices as u32).collect();
+        let mut removed = 0;
+        for (_, from, to) in candidates {
+            if touched[from as usize] || touched[to as usize] { continue }
+            let triangles = &adjacency[from as usize];
+            let flips = triangles.iter().any(|t| {
+                let corners = &res[t * 3..t * 3 + 3];
+                if corners.contains(&to) { return false }
+                let before = (position(corners[1]) - position(corners[0])).cross(position(corners[2]) - position(corners[0]));
+                let moved = |v: u32| if v == from { position(to) } else { position(v) };
+                let after = (moved(corners[1]) - moved(corners[0])).cross(moved(corners[2]) - moved(corners[0]));
+                // Also rejects turning a triangle nearly on its side, a fold that is a sliver at best
+                before.dot(after) <= 0.25 * before.magnitude() * after.magnitude()
+            });
+            if flips { continue }
+            remap[from as usize] = to;
+            for t in triangles {
+                for v in &res[t * 3..t * 3 + 3] {
+                    touched[*v as usize] = true;
+                }
+            }
+            let collapsed = quadrics[from as usize];
+            quadrics[to as usize].add(&collapsed);
+            removed += triangles.iter().filter(|t| res[*t * 3..*t * 3 + 3].contains(&to)).count() * 3;
+            if res.len() - removed <= target { break }
+        }
+        if removed == 0 { break }
+        let mut next = Vec::with_capacity(res.len() - removed);
+        for t in res.chunks(3) {
+            let t = [remap[t[0] as usize], remap[t[1] as usize], remap[t[2] as usize]];
+            if t[0] != t[1] && t[1] != t[2] && t[0] != t[2] {
+                next.extend_from_slice(&t);
+            }
+        }
+        res = next;
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Closed sphere sharing every vertex, `rings` rings of `segments` vertices between the poles.
+    fn sphere(rings: u32, segments: u32) -> (Vec<u32>, Vec<[f32;3]>) {
+        let mut positions = vec![[0., 1., 0.], [0., -1., 0.]];
+        for r in 1..=rings {
+            let v = r as f32 / (rings + 1) as f32 * std::f32::consts::PI;
+            for s in 0..segments {
+                let u = s as f32 / segments as f32 * std::f32::consts::TAU;
+                positions.push([u.cos() * v.sin(), v.cos(), u.sin() * v.sin()]);
+            }
+        }
+        let ring = |r: u32, s: u32| 2 + r * segments + s % segments;
+        let mut indices = Vec::new();
+        for s in 0..segments {
+            indices.extend_from_slice(&[0, ring(0, s + 1), ring(0, s)]);
+            indices.extend_from_slice(&[1, ring(rings - 1, s), ring(rings - 1, s + 1)]);
+            for r in 0..rings - 1 {
+                indices.extend_from_slice(&[ring(r, s), ring(r, s + 1), ring(r + 1, s)]);
+                indices.extend_from_slice(&[ring(r, s + 1), ring(r + 1, s + 1), ring(r + 1, s)]);
+            }
+        }
+        (indices, positions)
+    }
+    /// Bumpy `size` by `size` height field facing up, its middle column split by a UV seam.
+    fn terrain(size: u32) -> (Vec<u32>, Vec<[f32;3]>) {
+        let mut positions = Vec::new();
+        for z in 0..=size {
+            for x in 0..=size {
+                positions.push([x as f32, (x as f32 * 0.7).sin() * (z as f32 * 0.5).cos() * 0.3, z as f32]);
+            }
+        }
+        let seam = size / 2;
+        let first_copy = positions.len() as u32;
+        for z in 0..=size {
+            positions.push(positions[(z * (size + 1) + seam) as usize]);
+        }
+        let vertex = |x: u32, z: u32, right: bool| if x == seam && right { first_copy + z } else { z * (size + 1) + x };
+        let mut indices = Vec::new();
+        for z in 0..size {
+            for x in 0..size {
+                let right = x >= seam;
+                let (a, b, c, d) = (vertex(x, z, right), vertex(x + 1, z, right), vertex(x, z + 1, right), vertex(x + 1, z + 1, right));
+                indices.extend_from_slice(&[a, c, b, b, c, d]);
+            }
+        }
+        (indices, positions)
+    }
+    fn normal(t: &[u32], positions: &[[f32;3]]) -> Vector3<f32> {
+        let (a, b, c) = (Vector3::from(positions[t[0] as usize]), Vector3::from(positions[t[1] as usize]), Vector3::from(positions[t[2] as usize]));
+        (b - a).cross(c - a)
+    }
+
+    #[test]
+    fn reaches_the_target_on_a_closed_mesh() {
+        let (indices, positions) = sphere(16, 32);
+        let target = indices.len() / 4 / 3 * 3;
+        let res = simplify(&indices, &positions, None, target);
+        assert!(res.len() <= target && res.len() > target / 2, "{} indices for a target of {}", res.len(), target);
+        assert_eq!(res.len() % 3, 0);
+    }
+
+    #[test]
+    fn stops_when_every_vertex_is_locked() {
+        // A single row of quads only has border vertices
+        let (indices, positions) = terrain(1);
+        let res = simplify(&indices, &positions, None, 0);
+        assert_eq!(res, indices);
+    }
+
+    #[test]
+    fn keeps_seam_and_border_vertices() {
+        let size = 12;
+        let (indices, positions) = terrain(size);
+        let res = simplify(&indices, &positions, None, 0);
+        assert!(res.len() < indices.len());
+        let seam = size / 2;
+        for z in 0..=size {
+            let edge = (0..=size).filter(|x| z == 0 || z == size || *x == 0 || *x == size || *x == seam);
+            for v in edge.map(|x| z * (size + 1) + x).chain([(size + 1) * (size + 1) + z]) {
+                assert!(res.contains(&v), "vertex {} was collapsed", v);
+            }
+        }
+    }
+
+    #[test]
+    fn does_not_flip_or_degenerate_triangles() {
+        let (indices, positions) = terrain(12);
+        for target in [indices.len() / 2, 0] {
+            for t in simplify(&indices, &positions, None, target).chunks(3) {
+                assert!(t[0] != t[1] && t[1] != t[2] && t[0] != t[2], "{:?}", t);
+                assert!(normal(t, &positions).y > 0., "{:?} faces down", t);
+            }
+        }
+        let (indices, positions) = sphere(16, 32);
+        for t in simplify(&indices, &positions, None, indices.len() / 8).chunks(3) {
+            let n = normal(t, &positions);
+            assert!(n.magnitude() > 0., "{:?}", t);
+            let center = (Vector3::from(positions[t[0] as usize]) + Vector3::from(positions[t[1] as usize]) + Vector3::from(positions[t[2] as usize])) / 3.;
+            assert!(n.dot(center) > 0., "{:?} faces inward", t);
+        }
+    }
+
+    #[test]
+    fn collapses_stay_within_a_joint() {
+        let (indices, positions) = sphere(16, 32);
+        // Every vertex on its own joint can not collapse at all, one joint for all is like no joints
+        let joints: Vec<u16> = (0..positions.len() as u16).collect();
+        assert_eq!(simplify(&indices, &positions, Some(&joints), 0), indices);
+        let joints = vec![0; positions.len()];
+        let target = indices.len() / 4;
+        assert_eq!(simplify(&indices, &positions, Some(&joints), target), simplify(&indices, &positions, None, target));
+    }
+}
@@ -0,0 +1,991 @@
+use std::{path::{Path, PathBuf}, fs, collections::HashMap, time::Instant, sync::{Arc, Mutex}};
+use cgmath::{SquareMatrix, Matrix, Matrix3, Matrix4, Vector3, InnerSpace};
+
+#[path = "../src/cursor.rs"]
+mod cursor;
+#[path = "../src/low.rs"]
+mod low;
+#[path = "../src/keyframes.rs"]
+mod keyframes;
+#[path = "../src/bc.rs"]
+mod bc;
+#[path = "../src/material.rs"]
+mod material;
+#[path = "../src/nodes.rs"]
+mod nodes;
+use low::{Kind, Section, TextureKind, append_str};
+use material::{AlphaMode, MaterialInfo};
+mod cache;
+use cache::Cache;
+mod watch;
+mod pool;
+mod animation;
+mod compress;
+mod optimize;
+mod lod;
+mod tangents;
+mod skin;
+mod texture;
+mod scene;
+mod config;
+mod inspect;
+use config::{Config, VertexType};
+
+pub const ASSETS: &str = "./assets/models/";
+pub const COMPILED: &str = "./.compiled/models/";
+pub const CACHE: &str = "./.compiled/assets.cache";
+pub const ANIMATIONS_ASSETS: &str = "./assets/animations/";
+
+/// Directories the compiler reads and writes under a project root. Paths stored in compiled files
+/// stay relative to the root, which the game runs from.
+#[derive(Clone, Debug)]
+pub struct Dirs {
+    pub assets: PathBuf,
+    pub animations: PathBuf,
+    pub compiled: PathBuf,
+    pub compiled_animations: PathBuf,
+    pub cache: PathBuf
+}
+impl Dirs {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        let dir = |v: &str| root.as_ref().join(v.trim_start_matches("./"));
+        Self {
+            assets: dir(ASSETS),
+            animations: dir(ANIMATIONS_ASSETS),
+            compiled: dir(COMPILED),
+            compiled_animations: dir(animation::COMPILED),
+            cache: dir(CACHE)
+        }
+    }
+}
+
+pub struct Args {
+    pub force: bool,
+    pub watch: bool,
+    pub jobs: usize,
+    /// Compiled file to print instead of compiling the assets.
+    pub inspect: Option<PathBuf>,
+    /// glTF or PNG file the inspected file is exported to.
+    pub export: Option<PathBuf>
+}
+impl Args {
+    fn parse() -> Self {
+        let mut res = Self {
+            force: false,
+            watch: false,
+            jobs: std::thread::available_parallelism().map_or(4, |v| v.get()),
+            inspect: None,
+            export: None
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--force" => res.force = true,
+                "--watch" => res.watch = true,
+                "--jobs" | "-j" => res.jobs = match args.next().and_then(|v| v.parse().ok()) {
+                    Some(v) if v > 0 => v,
+                    _ => exit("--jobs expects a number of workers greater than 0")
+                },
+                "--inspect" => res.inspect = Some(args.next().unwrap_or_else(|| exit("--inspect expects a .low file")).into()),
+                "--export" => res.export = Some(args.next().unwrap_or_else(|| exit("--export expects a .gltf or .png file")).into()),
+                _ => exit(&format!("Unknown argument: {}\nUsage: compile [--force] [--watch] [--jobs N]\n       compile --inspect <file.low> [--export <file.gltf|file.png>]", arg))
+            }
+        }
+        if res.export.is_some() && res.inspect.is_none() { exit("--export needs --inspect") }
+        res
+    }
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2)
+}
+
+fn main() {
+    let args = Args::parse();
+    if let Some(path) = &args.inspect {
+        if let Err(e) = inspect::run(path, args.export.as_deref()) {
+            eprintln!("Error inspecting {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+        return
+    }
+    let dirs = Dirs::new(".");
+    initialize_folders(&dirs, args.force);
+    let failed = compile(&dirs, args.force, args.jobs);
+    if args.watch {
+        watch::run(&dirs, args.jobs);
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Compiles every asset whose source, dependencies or settings changed since the last run,
+/// returns the number of assets that failed.
+fn compile(dirs: &Dirs, force: bool, workers: usize) -> usize {
+    let start = Instant::now();
+    let cache = Arc::new(Mutex::new(if force { Cache::default() } else { Cache::load(&dirs.cache) }));
+    let mut jobs = Vec::new();
+    dir_loop(&dirs.assets, Config::default(), dirs, &mut jobs, &cache);
+    dir_loop(&dirs.animations, Config::default(), dirs, &mut jobs, &cache);
+    let failures = pool::run(jobs, workers);
+    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+    // A failed compile.conf skips its whole directory, whose outputs must survive until it is fixed
+    let failed: Vec<PathBuf> = failures.iter()
+        .map(|(path, _)| if path.ends_with("compile.conf") { path.parent().unwrap().to_path_buf() } else { path.clone() })
+        .collect();
+    for output in cache.remove_stale(&failed) {
+        println!("Removed: {}", output.display());
+    }
+    if let Err(e) = cache.save(&dirs.cache) {
+        println!("Error saving {}: {}", dirs.cache.display(), e);
+    }
+    println!("Assets compiled in {:.2}s, {} rebuilt, {} up to date, {} failed",
+        (Instant::now() - start).as_secs_f32(), cache.rebuilt(), cache.up_to_date(), failures.len());
+    if !failures.is_empty() {
+        println!("Failed assets:");
+        for (path, e) in &failures {
+            println!("    {}: {}", path.display(), e);
+        }
+    }
+    failures.len()
+}
+
+/// Queues a job for every asset under `path`, `conf` being the settings of the parent directory.
+fn dir_loop(path: impl AsRef<Path>, conf: Config, dirs: &Dirs, jobs: &mut Vec<(PathBuf, pool::Job)>, cache: &Arc<Mutex<Cache>>) {
+    let conf = match conf.directory(path.as_ref()) {
+        Ok(v) => v,
+        Err(e) => return jobs.push((path.as_ref().join("compile.conf"), Box::new(move || Err(e))))
+    };
+    let entries = match fs::read_dir(path.as_ref()) {
+        Ok(v) => v,
+        Err(e) => return println!("Error reading {}: {}", path.as_ref().display(), e)
+    };
+    for path in entries.flatten() {
+        let path = path.path();
+        if path.is_file() {
+            let ext = match path.extension() {
+                Some(v) => v,
+                None => continue
+            };
+            let conf = conf.clone();
+            let cache = cache.clone();
+            let job_path = path.clone();
+            let dirs = dirs.clone();
+            if path.starts_with(&dirs.animations) {
+                if ext == "gltf" || ext == "glb" {
+                    jobs.push((path, Box::new(move || animation::gltf(job_path, &dirs, conf, cache))));
+                }else if ext == "fbx" {
+                    jobs.push((path, Box::new(move || animation::fbx(job_path, &dirs, conf, cache))));
+                }
+            }else if ext == "gltf" || ext == "glb" {
+                jobs.push((path, Box::new(move || gltf(job_path, &dirs, conf, cache))));
+            }else if ext == "png" || ext == "jpg" || ext == "jpeg" {
+                jobs.push((path, Box::new(move || texture::image(job_path, &dirs, conf, cache))));
+            }
+        }
+        else if path.is_dir() {
+            dir_loop(path, conf.clone(), dirs, jobs, cache)
+        }
+    }
+}
+
+fn gltf(path: impl AsRef<Path>, dirs: &Dirs, conf: Config, cache: Arc<Mutex<Cache>>) -> Result<(), String> {
+    let conf = conf.file(path.as_ref())?;
+    if path.as_ref().file_name().unwrap().to_string_lossy().starts_with('_') { return Ok(()) }
+    let relative = path.as_ref().strip_prefix(&dirs.assets).unwrap().with_extension("low");
+    let output_path = dirs.compiled.join(&relative);
+    let scene_path = output_path.with_extension("scene.low");
+    let entry = cache::Entry::new(&path, &gltf_dependencies(path.as_ref()), format!("{:?}", conf));
+    let mut up_to_date = true;
+    for output in [&output_path, &scene_path] {
+        up_to_date &= cache.lock().unwrap().check(output, &entry);
+    }
+    if up_to_date { return Ok(()) }
+    let (gltf, buffers, _) = gltf::import(path.as_ref()).map_err(|e| e.to_string())?;
+    // Meshes without a skin use the static vertex type of the configured one
+    let conf = match skinned(&gltf) {
+        true => conf,
+        false => Config { vertex_type: conf.vertex_type.without_skin(), ..conf }
+    };
+    println!("OutputPath: {}, {:?}", output_path.display(), conf);
+    fs::create_dir_all(output_path.parent().unwrap()).map_err(|e| e.to_string())?;
+    let mut f = low::Writer::new(Kind::Mesh);
+    f.metadata(&[("source", &path.as_ref().to_string_lossy())]);
+
+    let slots = match conf.vertex_type {
+        VertexType::Basic => write_vertices(&mut f, &conf, &gltf, &buffers, &output_path, |b, p, i| {
+            append_vec3_f32(b, p.positions[i]);
+        })?,
+        VertexType::N => write_vertices(&mut f, &conf, &gltf, &buffers, &output_path, |b, p, i| {
+            append_vec3_f32(b, p.positions[i]);
+            append_vec3_f32(b, p.normals.as_ref().unwrap()[i]);
+            append_vec2_f32(b, p.uvs.as_ref().unwrap()[i]);
+        })?,
+        VertexType::NJW => write_vertices(&mut f, &conf, &gltf, &buffers, &output_path, |b, p, i| {
+            append_vec3_f32(b, p.positions[i]);
+            append_vec3_f32(b, p.normals.as_ref().unwrap()[i]);
+            append_vec2_f32(b, p.uvs.as_ref().unwrap()[i]);
+            append_joints(b, p.joints.as_ref().unwrap()[i]);
+            append_vec4_f32(b, p.weights.as_ref().unwrap()[i]);
+        })?,
+        VertexType::NT => write_vertices(&mut f, &conf, &gltf, &buffers, &output_path, |b, p, i| {
+            append_vec3_f32(b, p.positions[i]);
+            append_vec3_f32(b, p.normals.as_ref().unwrap()[i]);
+            append_vec4_f32(b, p.tangents.as_ref().unwrap()[i]);
+            append_vec2_f32(b, p.uvs.as_ref().unwrap()[i]);
+        })?,
+        VertexType::NTJW => write_vertices(&mut f, &conf, &gltf, &buffers, &output_path, |b, p, i| {
+            append_vec3_f32(b, p.positions[i]);
+            append_vec3_f32(b, p.normals.as_ref().unwrap()[i]);
+            append_vec4_f32(b, p.tangents.as_ref().unwrap()[i]);
+            append_vec2_f32(b, p.uvs.as_ref().unwrap()[i]);
+            append_joints(b, p.joints.as_ref().unwrap()[i]);
+            append_vec4_f32(b, p.weights.as_ref().unwrap()[i]);
+        })?
+    };
+    f.section(Section::Materials, materials_section(path.as_ref(), dirs, &gltf, &slots));
+    if conf.vertex_type.skinned() {
+        let skin = gltf.skins().next().ok_or_else(|| format!("{} mesh has no skin", conf.vertex_type.name()))?;
+        let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+        let ibms: Vec<[[f32; 4]; 4]> = reader.read_inverse_bind_matrices().ok_or("Skin has no inverse bind matrices")?.collect();
+        let skin_joints: Vec<gltf::Node> = skin.joints().collect();
+        let conversion = conf.conversion();
+        let inverse_conversion = conversion.invert().unwrap();
+        if skin_joints.len() >= u16::MAX as usize {
+            return Err(format!("Skeleton can not have more than {} joints, found {}", u16::MAX - 1, skin_joints.len()))
+        }
+        let mut b = (skin_joints.len() as u16).to_be_bytes().to_vec();
+        for (joint_id, joint) in skin_joints.iter().enumerate() {
+            let ibm = conversion * Matrix4::from(ibms[joint_id]) * inverse_conversion;
+            append_str(&mut b, &conf.rename_joint(joint.name().unwrap()));
+            b.extend_from_slice(&get_gltf_node_parent_id(&skin_joints, joint).to_be_bytes());
+            append_mat4x4(&mut b, ibm.invert().ok_or("Joint inverse bind matrix is not invertible")?.into());
+            append_mat4x4(&mut b, ibm.into());
+        }
+        f.section(Section::Skeleton, b);
+    }
+    f.save(&output_path).map_err(|e| e.to_string())?;
+    scene::write(path.as_ref(), &conf, &gltf, &Path::new(COMPILED).join(&relative), &scene_path)?;
+    cache.lock().unwrap().insert(&output_path, entry.clone());
+    cache.lock().unwrap().insert(&scene_path, entry);
+    Ok(())
+}
+
+/// Whether the glTF has a skin and a primitive with joints and weights to bind to it.
+fn skinned(gltf: &gltf::Document) -> bool {
+    gltf.skins().next().is_some() && gltf.meshes().flat_map(|v| v.primitives()).any(|v| {
+        v.get(&gltf::Semantic::Joints(0)).is_some() && v.get(&gltf::Semantic::Weights(0)).is_some()
+    })
+}
+
+/// The glTF file and the external buffers it references.
+fn gltf_dependencies(path: &Path) -> Vec<PathBuf> {
+    let mut dependencies = vec![path.to_path_buf()];
+    if let Ok(gltf) = gltf::Gltf::open(path) {
+        for buffer in gltf.buffers() {
+            if let gltf::buffer::Source::Uri(uri) = buffer.source() {
+                if !uri.starts_with("data:") {
+                    dependencies.push(path.parent().unwrap().join(uri));
+                }
+            }
+        }
+    }
+    dependencies
+}
+
+
+/// Position and normal delta of every vertex for each morph target.
+type Deltas = Vec<Vec<[[f32;3];2]>>;
+
+struct Primitive {
+    positions: Vec<[f32;3]>,
+    normals: Option<Vec<[f32;3]>>,
+    uvs: Option<Vec<[f32;2]>>,
+    joints: Option<Vec<[u16;4]>>,
+    weights: Option<Vec<[f32;4]>>,
+    /// Handedness in w.
+    tangents: Option<Vec<[f32;4]>>,
+    targets: Deltas
+}
+impl Primitive {
+    /// One vertex per index, for attributes computed per triangle corner.
+    fn unweld(&self, indices: &[u32]) -> Self {
+        fn corners<T: Copy>(v: &[T], indices: &[u32]) -> Vec<T> {
+            indices.iter().map(|i| v[*i as usize]).collect()
+        }
+        Self {
+            positions: corners(&self.positions, indices),
+            normals: self.normals.as_deref().map(|v| corners(v, indices)),
+            uvs: self.uvs.as_deref().map(|v| corners(v, indices)),
+            joints: self.joints.as_deref().map(|v| corners(v, indices)),
+            weights: self.weights.as_deref().map(|v| corners(v, indices)),
+            tangents: self.tangents.as_deref().map(|v| corners(v, indices)),
+            targets: self.targets.iter().map(|v| corners(v, indices)).collect()
+        }
+    }
+}
+
+type VertexWriter = fn(&mut Vec<u8>, &Primitive, usize);
+
+/// Writes the unique vertices of every primitive and an index buffer pointing into them,
+/// vertices with identical bytes are merged within a primitive. Non-indexed primitives get
+/// sequential indices. Every primitive is kept as a submesh with its own ranges and material slot.
+/// With `OptimizeMesh` triangle primitives are reordered and their before/after statistics printed.
+#[inline]
+fn write_vertices(
+    file: &mut low::Writer,
+    conf: &Config,
+    gltf: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+    output: &Path,
+    f: VertexWriter
+) -> Result<Vec<Option<usize>>, String> {
+    let conversion = conf.conversion();
+    let mut unique: HashMap<Vec<u8>, u32> = HashMap::new();
+    let mut indices: Vec<u32> = Vec::new();
+    let mut b: Vec<u8> = Vec::new();
+    let mut vertex = Vec::new();
+    let mut vertices = 0;
+    let mut submeshes = Vec::new();
+    // glTF material index of every slot, None being the default material
+    let mut slots: Vec<Option<usize>> = Vec::new();
+    let (mut before, mut after) = (optimize::Stats::default(), optimize::Stats::default());
+    if conf.lod_ratios.len() != conf.lod_distances.len() {
+        return Err(format!("LodRatios has {} levels but LodDistances has {}", conf.lod_ratios.len(), conf.lod_distances.len()))
+    }
+    // Indices of every level of detail and submesh, appended after the full detail indices
+    let mut lods: Vec<Vec<Vec<u32>>> = vec![Vec::new(); conf.lod_ratios.len()];
+    let mut bounds_positions = Vec::new();
+    // Morph targets of every mesh in order and the deltas of every primitive with its first vertex and target
+    let mut targets: Vec<(String, String, f32)> = Vec::new();
+    let mut deltas: Vec<(u32, usize, Deltas)> = Vec::new();
+    let skin_joints = gltf.skins().next().map_or(0, |v| v.joints().len());
+    let mut skin_stats = skin::Stats::default();
+    let mut rigid = 0;
+    for mesh in gltf.meshes() {
+        let primitives = mesh.primitives().len();
+        let first_target = targets.len();
+        let mesh_targets = mesh.primitives().map(|v| v.morph_targets().len()).max().unwrap_or(0);
+        if mesh_targets > 0 && !conf.vertex_type.skinned() {
+            println!("Warning: {} morph targets of mesh {} are dropped, they are only compiled for skinned vertex types", mesh_targets, mesh_name(&mesh));
+        } else if mesh_targets > 0 {
+            let names = target_names(&mesh);
+            for target in 0..mesh_targets {
+                targets.push((mesh_name(&mesh), names.get(target).cloned().unwrap_or_else(|| format!("target{}", target)),
+                    mesh.weights().and_then(|v| v.get(target)).copied().unwrap_or(0.)));
+            }
+        }
+        for primitive in mesh.primitives() {
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let mut p = Primitive {
+                positions: reader.read_positions().ok_or("Primitive has no positions")?
+                    .map(|v| (conversion * Vector3::from(v).extend(1.)).truncate().into()).collect(),
+                normals: reader.read_normals().map(|v| v.map(|v| (conversion * Vector3::from(v).extend(0.)).truncate().normalize().into()).collect()),
+                uvs: reader.read_tex_coords(0).map(|v| v.into_f32().collect()),
+                joints: reader.read_joints(0).map(|v| v.into_u16().collect()),
+                weights: reader.read_weights(0).map(|v| v.into_f32().collect()),
+                tangents: reader.read_tangents().map(|v| v.map(|v| {
+                    let t = (conversion * Vector3::new(v[0], v[1], v[2]).extend(0.)).truncate().normalize();
+                    [t.x, t.y, t.z, v[3]]
+                }).collect()),
+                targets: Vec::new()
+            };
+            if targets.len() > first_target {
+                if primitive.morph_targets().len() != mesh_targets {
+                    return Err(format!("Primitives of mesh {} have a different number of morph targets", mesh_name(&mesh)))
+                }
+                // Normal deltas are only rotated, the normals are unit length after the conversion
+                let convert = |v: [f32;3], scale: f32| (conversion * Vector3::from(v).extend(0.)).truncate().map(|c| c / scale).into();
+                let vertices = p.positions.len();
+                p.targets = reader.read_morph_targets().map(|(positions, normals, _)| {
+                    let positions: Vec<[f32;3]> = positions.map_or(vec![[0.;3]; vertices], |v| v.map(|v| convert(v, 1.)).collect());
+                    let normals: Vec<[f32;3]> = normals.map_or(vec![[0.;3]; vertices], |v| v.map(|v| convert(v, conf.scale)).collect());
+                    positions.into_iter().zip(normals).map(|(p, n)| [p, n]).collect()
+                }).collect();
+                if p.targets.iter().any(|v| v.len() != vertices) {
+                    return Err(format!("Morph targets of mesh {} do not match its vertices", mesh_name(&mesh)))
+                }
+            }
+            let vertex_type = conf.vertex_type;
+            if vertex_type.skinned() && p.joints.is_none() && p.weights.is_none() {
+                rigid_bind(&mut p, gltf, &mesh, conversion);
+                rigid += 1;
+            }
+            if vertex_type != VertexType::Basic && (p.normals.is_none() || p.uvs.is_none()) {
+                return Err(format!("Mesh {} needs normals and uvs for {}", mesh.name().unwrap_or(""), vertex_type.name()))
+            }
+            if vertex_type.skinned() && (p.joints.is_none() || p.weights.is_none()) {
+                return Err(format!("Mesh {} needs joints and weights for {}", mesh.name().unwrap_or(""), vertex_type.name()))
+            }
+            if vertex_type.skinned() {
+                let mut sets = vec![(p.joints.take().unwrap(), p.weights.take().unwrap())];
+                if let (Some(joints), Some(weights)) = (reader.read_joints(1), reader.read_weights(1)) {
+                    sets.push((joints.into_u16().collect(), weights.into_f32().collect()));
+                }
+                if sets.iter().any(|v| v.0.len() != p.positions.len() || v.1.len() != p.positions.len()) {
+                    return Err(format!("Joints and weights of mesh {} do not match its vertices", mesh.name().unwrap_or("")))
+                }
+                let ((joints, weights), stats) = skin::clean(&sets, skin_joints, conf.min_weight);
+                (p.joints, p.weights) = (Some(joints), Some(weights));
+                skin_stats.add(stats);
+            }
+            let mut primitive_indices: Vec<u32> = match reader.read_indices() {
+                Some(v) => v.into_u32().collect(),
+                None => (0..p.positions.len() as u32).collect()
+            };
+            let source = (primitive_indices.clone(), p.positions.len());
+            if vertex_type.tangents() && p.tangents.is_none() {
+                let (normals, uvs) = (p.normals.as_deref().unwrap(), p.uvs.as_deref().unwrap());
+                if primitive.mode() == gltf::mesh::Mode::Triangles {
+                    // Tangents are per corner, the vertices sharing them are merged again below
+                    let tangents = tangents::generate(&p.positions, normals, uvs, &primitive_indices);
+                    p = p.unweld(&primitive_indices);
+                    p.tangents = Some(tangents);
+                    primitive_indices = (0..primitive_indices.len() as u32).collect();
+                }
+                else {
+                    p.tangents = Some(normals.iter().map(|v| tangents::perpendicular(Vector3::from(*v))).collect());
+                }
+            }
+            let mut submesh = Submesh {
+                name: match (mesh.name(), primitives) {
+                    (Some(name), 1) => name.to_string(),
+                    (Some(name), _) => format!("{}.{}", name, primitive.index()),
+                    (None, _) => format!("mesh{}.{}", mesh.index(), primitive.index())
+                },
+                material: match slots.iter().position(|v| *v == primitive.material().index()) {
+                    Some(v) => v,
+                    None => {
+                        slots.push(primitive.material().index());
+                        slots.len() - 1
+                    }
+                },
+                first_index: indices.len() as u32,
+                indices_len: 0,
+                first_vertex: vertices,
+                vertices_len: 0
+            };
+            unique.clear();
+            let mut remap = vec![u32::MAX; p.positions.len()];
+            let mut primitive_vertices = Vec::new();
+            let mut positions = Vec::new();
+            // Joint with the largest weight of every vertex, simplification keeps their boundaries
+            let mut dominant_joints = Vec::new();
+            let mut local_indices = Vec::with_capacity(primitive_indices.len());
+            let mut primitive_deltas = vec![Vec::new(); p.targets.len()];
+            for idx in &primitive_indices {
+                let idx = *idx as usize;
+                if remap[idx] == u32::MAX {
+                    vertex.clear();
+                    f(&mut vertex, &p, idx);
+                    // Vertices are only merged when their morph deltas are the same too
+                    let stride = vertex.len();
+                    for v in p.targets.iter().flat_map(|v| v[idx].as_flattened()) {
+                        vertex.extend_from_slice(&v.to_bits().to_be_bytes());
+                    }
+                    let next = unique.len() as u32;
+                    remap[idx] = *unique.entry(vertex.clone()).or_insert_with(|| {
+                        primitive_vertices.extend_from_slice(&vertex[..stride]);
+                        for (deltas, target) in primitive_deltas.iter_mut().zip(&p.targets) {
+                            deltas.push(target[idx]);
+                        }
+                        positions.push(p.positions[idx]);
+                        if let (true, Some(joints), Some(weights)) = (vertex_type.skinned(), &p.joints, &p.weights) {
+                            let k = (0..4).max_by(|a, b| weights[idx][*a].total_cmp(&weights[idx][*b])).unwrap();
+                            dominant_joints.push(joints[idx][k]);
+                        }
+                        next
+                    });
+                }
+                local_indices.push(remap[idx]);
+            }
+            if conf.optimize_mesh && primitive.mode() == gltf::mesh::Mode::Triangles && !positions.is_empty() {
+                let stride = primitive_vertices.len() / positions.len();
+                before.add(optimize::Stats::new(&source.0, source.1, stride));
+                let order = optimize::optimize(&mut local_indices, &mut primitive_vertices, &positions, stride);
+                positions = reorder(&positions, &order);
+                if !dominant_joints.is_empty() { dominant_joints = reorder(&dominant_joints, &order) }
+                for deltas in &mut primitive_deltas {
+                    *deltas = reorder(deltas, &order);
+                }
+                after.add(optimize::Stats::new(&local_indices, positions.len(), stride));
+            }
+            for (level, ratio) in conf.lod_ratios.iter().enumerate() {
+                let mut lod = local_indices.clone();
+                if primitive.mode() == gltf::mesh::Mode::Triangles {
+                    let target = ((lod.len() / 3) as f32 * ratio).round() as usize * 3;
+                    lod = lod::simplify(&lod, &positions, Some(dominant_joints.as_slice()).filter(|v| !v.is_empty()), target);
+                    if conf.optimize_mesh {
+                        optimize::vertex_cache(&mut lod, positions.len());
+                        optimize::overdraw(&mut lod, &positions);
+                    }
+                }
+                lods[level].push(lod.iter().map(|v| v + vertices).collect::<Vec<_>>());
+            }
+            if !primitive_deltas.is_empty() {
+                deltas.push((vertices, first_target, primitive_deltas));
+            }
+            b.extend_from_slice(&primitive_vertices);
+            bounds_positions.extend_from_slice(&positions);
+            indices.extend(local_indices.iter().map(|v| v + vertices));
+            submesh.indices_len = indices.len() as u32 - submesh.first_index;
+            submesh.vertices_len = unique.len() as u32;
+            vertices += submesh.vertices_len;
+            submeshes.push(submesh);
+        }
+    }
+    if submeshes.len() > u16::MAX as usize { return Err(format!("Meshes can not have more than {} primitives", u16::MAX)) }
+    if conf.optimize_mesh {
+        println!("Mesh: {}, ACMR {:.3} -> {:.3}, {} -> {} vertices, {} -> {} bytes",
+            output.display(), before.acmr(), after.acmr(), before.vertices, after.vertices, before.bytes, after.bytes);
+    }
+    if conf.vertex_type.skinned() {
+        println!("Skin: {}, {} vertices renormalized, {} influences pruned, {} vertices reduced to 4 influences, {} primitives bound to one joint",
+            output.display(), skin_stats.renormalized, skin_stats.pruned, skin_stats.reduced, rigid);
+        if skin_stats.zero > 0 {
+            println!("Warning: {}, {} vertices have no weight and are bound to joint 0", output.display(), skin_stats.zero);
+        }
+        if skin_stats.outside > 0 {
+            println!("Warning: {}, {} vertices are weighted to joints outside of the skin of {} joints", output.display(), skin_stats.outside, skin_joints);
+        }
+    }
+    let lods_section = lods_section(conf, &bounds_positions, &lods, &mut indices);
+    if !lods.is_empty() {
+        let triangles: Vec<String> = [submeshes.iter().map(|v| v.indices_len as usize).sum::<usize>()].into_iter()
+            .chain(lods.iter().map(|level| level.iter().map(|v| v.len()).sum()))
+            .map(|v| (v / 3).to_string())
+            .collect();
+        println!("Lods: {}, {} triangles", output.display(), triangles.join(" -> "));
+    }
+    let mut section = Vec::with_capacity(b.len() + 16);
+    append_str(&mut section, conf.vertex_type.name());
+    section.extend_from_slice(&vertices.to_be_bytes());
+    section.extend_from_slice(&b);
+    file.section(Section::Vertices, section);
+    file.section(Section::Indices, indices_section(&indices, vertices));
+
+    let mut section = (submeshes.len() as u16).to_be_bytes().to_vec();
+    for submesh in &submeshes {
+        append_str(&mut section, &submesh.name);
+        section.extend_from_slice(&(submesh.material as u16).to_be_bytes());
+        for v in [submesh.first_index, submesh.indices_len, submesh.first_vertex, submesh.vertices_len] {
+            section.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+    file.section(Section::Submeshes, section);
+    file.section(Section::Lods, lods_section);
+    if !targets.is_empty() {
+        targets_sections(file, &targets, &deltas, vertices);
+        println!("Targets: {}, {} targets, {} bytes of deltas", output.display(), targets.len(), targets.len() * vertices as usize * 24);
+    }
+    Ok(slots)
+}
+
+/// Name, target name and default weight of every morph target, then the position and the normal
+/// deltas of every target and vertex. Vertices outside of the mesh of a target have no delta.
+fn targets_sections(file: &mut low::Writer, targets: &[(String, String, f32)], deltas: &[(u32, usize, Deltas)], vertices: u32) {
+    let mut b = (targets.len() as u16).to_be_bytes().to_vec();
+    for (mesh, name, weight) in targets {
+        append_str(&mut b, mesh);
+        append_str(&mut b, name);
+        b.extend_from_slice(&weight.to_be_bytes());
+    }
+    file.section(Section::Targets, b);
+    let mut all = vec![[[0.;3];2]; targets.len() * vertices as usize];
+    for (first_vertex, first_target, primitive) in deltas {
+        for (target, values) in primitive.iter().enumerate() {
+            let start = (first_target + target) * vertices as usize + *first_vertex as usize;
+            all[start..start + values.len()].copy_from_slice(values);
+        }
+    }
+    for (section, k) in [(Section::PositionDeltas, 0), (Section::NormalDeltas, 1)] {
+        let mut b = Vec::with_capacity(all.len() * 12);
+        for v in &all {
+            append_vec3_f32(&mut b, v[k]);
+        }
+        file.section(section, b);
+    }
+}
+
+/// Binds a primitive without joints and weights, like a weapon under a hand, to the closest joint
+/// at or above the first node drawing its mesh. Its vertices are moved to skin space with the rest
+/// transform of that node, joint 0 is used when no joint is above it.
+fn rigid_bind(p: &mut Primitive, gltf: &gltf::Document, mesh: &gltf::Mesh, conversion: Matrix4<f32>) {
+    let mut parents = vec![None; gltf.nodes().len()];
+    for node in gltf.nodes() {
+        for child in node.children() {
+            parents[child.index()] = Some(node.index());
+        }
+    }
+    let joints: Vec<usize> = gltf.skins().next().map(|v| v.joints().map(|v| v.index()).collect()).unwrap_or_default();
+    let (mut joint, mut matrix) = (None, Matrix4::identity());
+    let mut node = gltf.nodes().find(|v| v.mesh().is_some_and(|v| v.index() == mesh.index())).map(|v| v.index());
+    while let Some(id) = node {
+        joint = joint.or_else(|| joints.iter().position(|v| *v == id));
+        matrix = Matrix4::from(gltf.nodes().nth(id).unwrap().transform().matrix()) * matrix;
+        node = parents[id];
+    }
+    let matrix = conversion * matrix * conversion.invert().unwrap();
+    let rotation = Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+    let normal_matrix = rotation.invert().unwrap_or(rotation).transpose();
+    let direction = |m: &Matrix3<f32>, v: [f32;3]| -> [f32;3] { (m * Vector3::from(v)).normalize().into() };
+    for v in &mut p.positions {
+        *v = (matrix * Vector3::from(*v).extend(1.)).truncate().into();
+    }
+    for v in p.normals.iter_mut().flatten() {
+        *v = direction(&normal_matrix, *v);
+    }
+    for v in p.tangents.iter_mut().flatten() {
+        let t = direction(&rotation, [v[0], v[1], v[2]]);
+        *v = [t[0], t[1], t[2], v[3]];
+    }
+    for delta in p.targets.iter_mut().flatten() {
+        delta[0] = (rotation * Vector3::from(delta[0])).into();
+        delta[1] = (normal_matrix * Vector3::from(delta[1])).into();
+    }
+    let joint = joint.unwrap_or(0) as u16;
+    p.joints = Some(vec![[joint, 0, 0, 0]; p.positions.len()]);
+    p.weights = Some(vec![[1., 0., 0., 0.]; p.positions.len()]);
+}
+
+/// Name of a glTF mesh, `mesh<index>` when it has none. Morph weights are matched by it.
+pub fn mesh_name(mesh: &gltf::Mesh) -> String {
+    mesh.name().map_or_else(|| format!("mesh{}", mesh.index()), str::to_string)
+}
+
+/// Morph target names from the `targetNames` extras exporters write.
+fn target_names(mesh: &gltf::Mesh) -> Vec<String> {
+    let Some(extras) = mesh.extras() else { return Vec::new() };
+    let Ok(value) = gltf::json::deserialize::from_str::<gltf::json::Value>(extras.get()) else { return Vec::new() };
+    value.get("targetNames").and_then(|v| v.as_array())
+        .map(|v| v.iter().map(|v| v.as_str().unwrap_or("").to_string()).collect())
+        .unwrap_or_default()
+}
+
+fn reorder<T: Copy + Default>(values: &[T], order: &[u32]) -> Vec<T> {
+    let mut res = vec![T::default(); values.len()];
+    for (v, i) in values.iter().zip(order) {
+        if *i != u32::MAX { res[*i as usize] = *v }
+    }
+    res
+}
+
+/// Bounding sphere of the mesh, then the camera distance and the index range of every submesh
+/// for each level of detail. The indices of the levels are appended to `indices`.
+fn lods_section(conf: &Config, positions: &[[f32;3]], lods: &[Vec<Vec<u32>>], indices: &mut Vec<u32>) -> Vec<u8> {
+    let (mut min, mut max) = ([f32::MAX;3], [f32::MIN;3]);
+    for p in positions {
+        for k in 0..3 {
+            min[k] = min[k].min(p[k]);
+            max[k] = max[k].max(p[k]);
+        }
+    }
+    let center = if positions.is_empty() { Vector3::new(0., 0., 0.) } else { (Vector3::from(min) + Vector3::from(max)) * 0.5 };
+    let radius = positions.iter().map(|p| (Vector3::from(*p) - center).magnitude()).fold(0., f32::max);
+    let mut b = Vec::new();
+    append_vec3_f32(&mut b, center.into());
+    b.extend_from_slice(&radius.to_be_bytes());
+    b.push(lods.len() as u8);
+    for (level, distance) in lods.iter().zip(&conf.lod_distances) {
+        b.extend_from_slice(&distance.to_be_bytes());
+        for submesh in level {
+            b.extend_from_slice(&(indices.len() as u32).to_be_bytes());
+            b.extend_from_slice(&(submesh.len() as u32).to_be_bytes());
+            indices.extend_from_slice(submesh);
+        }
+    }
+    b
+}
+
+/// One material per slot, textures point to the outputs of the texture compiler.
+fn materials_section(path: &Path, dirs: &Dirs, gltf: &gltf::Document, slots: &[Option<usize>]) -> Vec<u8> {
+    let mut b = (slots.len() as u16).to_be_bytes().to_vec();
+    for slot in slots {
+        let material = match slot.and_then(|v| gltf.materials().nth(v)) {
+            Some(v) => v,
+            None => {
+                MaterialInfo::default().write(&mut b);
+                continue
+            }
+        };
+        let pbr = material.pbr_metallic_roughness();
+        let texture = |v: Option<gltf::Texture>, kind: TextureKind| v.and_then(|v| texture_output(path, dirs, &v, kind));
+        MaterialInfo {
+            name: material.name().map_or_else(|| format!("material{}", material.index().unwrap()), str::to_string),
+            base_color: pbr.base_color_factor(),
+            base_color_texture: texture(pbr.base_color_texture().map(|v| v.texture()), TextureKind::Color),
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            metallic_roughness_texture: texture(pbr.metallic_roughness_texture().map(|v| v.texture()), TextureKind::Linear),
+            normal_texture: texture(material.normal_texture().map(|v| v.texture()), TextureKind::Normal),
+            normal_scale: material.normal_texture().map_or(1., |v| v.scale()),
+            occlusion_texture: texture(material.occlusion_texture().map(|v| v.texture()), TextureKind::Mask),
+            occlusion_strength: material.occlusion_texture().map_or(1., |v| v.strength()),
+            emissive: material.emissive_factor(),
+            emissive_texture: texture(material.emissive_texture().map(|v| v.texture()), TextureKind::Color),
+            alpha_mode: match material.alpha_mode() {
+                gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+                gltf::material::AlphaMode::Blend => AlphaMode::Blend
+            },
+            alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5)
+        }.write(&mut b);
+    }
+    b
+}
+
+/// Compiled path of a texture image, warns when the image is embedded, outside of the assets
+/// or compiled as another kind than the material expects. Occlusion is read from red so it
+/// can share a linear texture.
+fn texture_output(path: &Path, dirs: &Dirs, texture: &gltf::Texture, kind: TextureKind) -> Option<String> {
+    let uri = match texture.source().source() {
+        gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => uri,
+        _ => {
+            println!("Warning: {} embeds image {}, only image files are supported", path.display(), texture.source().index());
+            return None
+        }
+    };
+    let image = path.parent().unwrap().join(uri);
+    let relative = match image.strip_prefix(&dirs.assets) {
+        Ok(v) if image.is_file() => v,
+        _ => {
+            println!("Warning: {} uses {} which is not an image in {}", path.display(), image.display(), dirs.assets.display());
+            return None
+        }
+    };
+    match Config::asset(&dirs.assets, &image) {
+        Ok(conf) if conf.texture_kind != kind && !(kind == TextureKind::Mask && conf.texture_kind == TextureKind::Linear) => println!(
+            "Warning: {} is used as {:?} by {} but compiled as {:?}, set TextureKind in {}.conf",
+            image.display(), kind, path.display(), conf.texture_kind, image.display()
+        ),
+        _ => {}
+    }
+    Some(Path::new(COMPILED).join(relative).with_extension("low").to_string_lossy().into_owned())
+}
+
+/// One glTF primitive and the ranges of the vertex and index buffers it uses.
+struct Submesh {
+    name: String,
+    /// Material slot index.
+    material: usize,
+    first_index: u32,
+    indices_len: u32,
+    first_vertex: u32,
+    vertices_len: u32
+}
+
+/// Index size in bytes (2 or 4), index count, then the indices.
+fn indices_section(indices: &[u32], vertices: u32) -> Vec<u8> {
+    let wide = vertices > u16::MAX as u32;
+    let mut b = Vec::with_capacity(5 + indices.len() * if wide { 4 } else { 2 });
+    b.push(if wide { 4 } else { 2 });
+    b.extend_from_slice(&(indices.len() as u32).to_be_bytes());
+    for i in indices {
+        if wide { b.extend_from_slice(&i.to_be_bytes()) }
+        else { b.extend_from_slice(&(*i as u16).to_be_bytes()) }
+    }
+    b
+}
+
+/// Index of the parent joint, `u16::MAX` for roots.
+fn get_gltf_node_parent_id(joints: &[gltf::Node], j: &gltf::Node) -> u16 {
+    for (parent_id, joint) in joints.iter().enumerate() {
+        for child in joint.children() {
+            if child.index() == j.index() {
+                return parent_id as u16
+            }
+        }
+    }
+    u16::MAX
+}
+
+fn initialize_folders(dirs: &Dirs, force: bool) {
+    fs::create_dir_all(&dirs.assets).unwrap();
+    fs::create_dir_all(&dirs.animations).unwrap();
+    fs::create_dir_all(&dirs.compiled_animations).unwrap();
+    if force {
+        fs::remove_dir_all(&dirs.compiled).unwrap_or_default();
+    }
+    fs::create_dir_all(&dirs.compiled).unwrap();
+}
+
+#[inline]
+fn append_f32(b: &mut Vec<u8>, v: f32) {
+    let v = v.to_be_bytes();
+    b.push(v[0]); b.push(v[1]); b.push(v[2]); b.push(v[3]);
+}
+#[inline]
+fn append_vec3_f32(b: &mut Vec<u8>, v: [f32;3]) {
+    append_f32(b, v[0]); append_f32(b, v[1]); append_f32(b, v[2])
+}
+#[inline]
+fn append_vec2_f32(b: &mut Vec<u8>, v: [f32;2]) {
+    append_f32(b, v[0]); append_f32(b, v[1])
+}
+#[inline]
+fn append_vec4_f32(b: &mut Vec<u8>, v: [f32;4]) {
+    append_f32(b, v[0]); append_f32(b, v[1]); append_f32(b, v[2]); append_f32(b, v[3])
+}
+#[inline]
+fn append_joints(b: &mut Vec<u8>, v: [u16;4]) {
+    for joint in v {
+        b.extend_from_slice(&joint.to_be_bytes());
+    }
+}
+#[inline]
+fn append_mat4x4(b: &mut Vec<u8>, v: [[f32;4];4]) {
+    append_vec4_f32(b, v[0]); append_vec4_f32(b, v[1]); append_vec4_f32(b, v[2]); append_vec4_f32(b, v[3])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One triangle with positions only and its buffer file.
+    fn write_triangle(path: &Path) {
+        let positions: Vec<u8> = [0f32, 0., 0., 1., 0., 0., 0., 1., 0.].iter().flat_map(|v| v.to_le_bytes()).collect();
+        fs::write(path.with_extension("bin"), &positions).unwrap();
+        let json = format!(r#"{{"asset":{{"version":"2.0"}},"scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],
+            "meshes":[{{"name":"triangle","primitives":[{{"attributes":{{"POSITION":0}}}}]}}],
+            "buffers":[{{"byteLength":36,"uri":"{}"}}],"bufferViews":[{{"buffer":0,"byteLength":36}}],
+            "accessors":[{{"bufferView":0,"componentType":5126,"count":3,"type":"VEC3","min":[0,0,0],"max":[1,1,0]}}]}}"#,
+            path.with_extension("bin").file_name().unwrap().to_string_lossy());
+        fs::write(path, json).unwrap();
+    }
+
+    #[test]
+    fn rigid_attachment_is_placed_once() {
+        let root = std::env::temp_dir().join(format!("compile-rigid-{}", std::process::id()));
+        fs::remove_dir_all(&root).unwrap_or_default();
+        let dirs = Dirs::new(&root);
+        fs::create_dir_all(dirs.assets.join("rigid")).unwrap();
+        // A skinned triangle and a sword without joints under the hand joint, both share the triangle
+        let floats = |v: &[f32]| v.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        let mut b = floats(&[0., 0., 0., 1., 0., 0., 0., 1., 0.]);
+        b.extend(floats(&[0., 0., 1., 0., 0., 1., 0., 0., 1.]));
+        b.extend(floats(&[0., 0., 1., 0., 0., 1.]));
+        b.extend([0; 12]);
+        b.extend(floats(&[1., 0., 0., 0., 1., 0., 0., 0., 1., 0., 0., 0.]));
+        b.extend(floats(&[1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., -2., 0., 1.]));
+        fs::write(dirs.assets.join("rigid/rigid.bin"), &b).unwrap();
+        let views: Vec<String> = [(0, 36), (36, 36), (72, 24), (96, 12), (108, 48), (156, 64)].iter()
+            .map(|(offset, length)| format!(r#"{{"buffer":0,"byteOffset":{},"byteLength":{}}}"#, offset, length)).collect();
+        let json = format!(r#"{{"asset":{{"version":"2.0"}},"scene":0,"scenes":[{{"nodes":[0,1]}}],
+            "nodes":[{{"name":"body","mesh":0,"skin":0}},{{"name":"hand","translation":[0,2,0],"children":[2]}},{{"name":"sword","mesh":1,"translation":[1,0,0]}}],
+            "skins":[{{"joints":[1],"inverseBindMatrices":5}}],
+            "meshes":[{{"name":"body","primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1,"TEXCOORD_0":2,"JOINTS_0":3,"WEIGHTS_0":4}}}}]}},
+                {{"name":"sword","primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1,"TEXCOORD_0":2}}}}]}}],
+            "buffers":[{{"byteLength":{},"uri":"rigid.bin"}}],"bufferViews":[{}],
+            "accessors":[{{"bufferView":0,"componentType":5126,"count":3,"type":"VEC3","min":[0,0,0],"max":[1,1,0]}},
+                {{"bufferView":1,"componentType":5126,"count":3,"type":"VEC3"}},{{"bufferView":2,"componentType":5126,"count":3,"type":"VEC2"}},
+                {{"bufferView":3,"componentType":5121,"count":3,"type":"VEC4"}},{{"bufferView":4,"componentType":5126,"count":3,"type":"VEC4"}},
+                {{"bufferView":5,"componentType":5126,"count":1,"type":"MAT4"}}]}}"#, b.len(), views.join(","));
+        fs::write(dirs.assets.join("rigid/rigid.gltf"), json).unwrap();
+        fs::write(dirs.assets.join("rigid/compile.conf"), "VertexType=NJW\n").unwrap();
+        initialize_folders(&dirs, false);
+        assert_eq!(compile(&dirs, false, 1), 0);
+
+        // The sword vertices hold its rest pose in the skin, bound to the hand
+        let mesh = low::File::read(dirs.compiled.join("rigid/rigid.low"), Kind::Mesh).unwrap();
+        let mut cursor = mesh.require(Section::Vertices).unwrap();
+        assert_eq!(cursor.read_str(), "NJW");
+        let vertices: Vec<([f32;3], [u16;4])> = (0..cursor.read_u32()).map(|_| {
+            let position = cursor.read_vec3();
+            // Normal and uv
+            cursor.read_bytes(20);
+            let joints = cursor.read_joints();
+            cursor.read_vec4();
+            (position, joints)
+        }).collect();
+        assert_eq!(vertices.len(), 6);
+        for ((position, joints), source) in vertices[3..].iter().zip([[1., 2., 0.], [2., 2., 0.], [1., 3., 0.]]) {
+            assert_eq!((*position, joints[0]), (source, 0));
+        }
+
+        // So the scene places it at the root like the skinned body, the node transform is not applied again
+        let scene = low::File::read(dirs.compiled.join("rigid/rigid.scene.low"), Kind::Scene).unwrap();
+        let mut cursor = scene.require(Section::Nodes).unwrap();
+        cursor.read_str();
+        let nodes: Vec<nodes::Node> = (0..cursor.read_u16()).map(|_| nodes::Node::read(&mut cursor).unwrap()).collect();
+        let skins: Vec<(&str, Option<usize>)> = nodes.iter().map(|v| (v.name.as_str(), v.skin)).collect();
+        assert_eq!(skins, [("body", Some(0)), ("hand", None), ("sword", Some(0))]);
+        fs::remove_dir_all(&root).unwrap_or_default();
+    }
+
+    #[test]
+    fn fbx_keys_are_compressed() {
+        use keyframes::{Channel, Encoding, Interpolation, Property, Track, Trs};
+        let root = std::env::temp_dir().join(format!("compile-fbx-{}", std::process::id()));
+        fs::remove_dir_all(&root).unwrap_or_default();
+        let dirs = Dirs::new(&root);
+        fs::create_dir_all(dirs.animations.join("rig")).unwrap();
+        fs::write(dirs.animations.join("rig/wave.fbx"), "").unwrap();
+        fs::write(dirs.animations.join("rig/compile.conf"), "JointReplace=mixamorig: ->\nJointCase=Lower\n").unwrap();
+        initialize_folders(&dirs, false);
+        // Blender has not exported the keys yet
+        assert_eq!(compile(&dirs, false, 1), 1);
+
+        // Every frame as compiler.py samples it, a constant translation and a rotation turning at a constant speed
+        let rotation = |angle: f32| [0., 0., (angle / 2.).sin(), (angle / 2.).cos()];
+        let times = vec![0., 0.5, 1.];
+        let channel = |property, values| Channel { property, interpolation: Interpolation::Linear, encoding: Encoding::Float, times: times.clone(), values };
+        let track = Track {
+            name: "mixamorig:Arm".into(),
+            rest: Trs { translation: [0., 1., 0.], rotation: rotation(0.), scale: [1.;3] },
+            channels: vec![channel(Property::Translation, vec![[0., 1., 0., 0.]; 3]), channel(Property::Rotation, vec![rotation(0.), rotation(0.5), rotation(1.)])]
+        };
+        let mut b = 1f32.to_be_bytes().to_vec();
+        b.extend_from_slice(&1u16.to_be_bytes());
+        track.write(&mut b);
+        let mut f = low::Writer::new(Kind::Animation);
+        f.section(Section::Channels, b);
+        fs::create_dir_all(dirs.compiled_animations.join("rig")).unwrap();
+        f.save(dirs.compiled_animations.join("rig/wave.keys.low")).unwrap();
+        assert_eq!(compile(&dirs, false, 1), 0);
+
+        let clip = low::File::read(dirs.compiled_animations.join("rig/wave.low"), Kind::Animation).unwrap();
+        let mut cursor = clip.require(Section::Channels).unwrap();
+        assert_eq!((cursor.read_f32(), cursor.read_u16()), (1., 1));
+        let track = Track::read(&mut cursor).unwrap();
+        assert_eq!(track.name, "arm");
+        let channels: Vec<_> = track.channels.iter().map(|v| (v.property, v.encoding, v.times.len())).collect();
+        assert_eq!(channels, [(Property::Rotation, Encoding::SmallestThree, 2)]);
+        fs::remove_dir_all(&root).unwrap_or_default();
+    }
+
+    #[test]
+    fn invalid_conf_keeps_compiled_assets() {
+        let root = std::env::temp_dir().join(format!("compile-conf-{}", std::process::id()));
+        fs::remove_dir_all(&root).unwrap_or_default();
+        let dirs = Dirs::new(&root);
+        fs::create_dir_all(dirs.assets.join("props")).unwrap();
+        let source = dirs.assets.join("props/triangle.gltf");
+        let output = dirs.compiled.join("props/triangle.low");
+        write_triangle(&source);
+        fs::write(dirs.assets.join("props/compile.conf"), "VertexType=Basic\n").unwrap();
+        initialize_folders(&dirs, false);
+        assert_eq!(compile(&dirs, false, 1), 0);
+        assert!(output.is_file());
+
+        // A typo in the directory settings fails the directory without removing what it compiled before
+        fs::write(dirs.assets.join("props/compile.conf"), "VertexType=Basic\nScale=big\n").unwrap();
+        assert_eq!(compile(&dirs, false, 1), 1);
+        assert!(output.is_file());
+
+        // Same for the settings of the asset itself
+        fs::write(dirs.assets.join("props/compile.conf"), "VertexType=Basic\n").unwrap();
+        fs::write(dirs.assets.join("props/triangle.gltf.conf"), "UpAxis=X\n").unwrap();
+        assert_eq!(compile(&dirs, false, 1), 1);
+        assert!(output.is_file());
+
+        // Removed sources still remove their outputs
+        fs::remove_file(dirs.assets.join("props/triangle.gltf.conf")).unwrap();
+        assert_eq!(compile(&dirs, false, 1), 0);
+        fs::remove_file(&source).unwrap();
+        assert_eq!(compile(&dirs, false, 1), 0);
+        assert!(!output.exists());
+        fs::remove_dir_all(&root).unwrap_or_default();
+    }
+}
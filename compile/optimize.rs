@@ -0,0 +1,306 @@
+use std::collections::VecDeque;
+use cgmath::{InnerSpace, Vector3};
+
+/// Size of the FIFO cache ACMR is measured with, close to the post-transform cache of most GPUs.
+const FIFO_SIZE: usize = 16;
+/// Size of the LRU cache the triangle order is scored against.
+const LRU_SIZE: usize = 32;
+
+/// Before or after statistics of a mesh, summed over its submeshes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub triangles: usize,
+    /// Vertices transformed by a FIFO cache of `FIFO_SIZE` entries.
+    pub misses: usize,
+    pub vertices: usize,
+    pub bytes: usize
+}
+impl Stats {
+    pub fn new(indices: &[u32], vertices: usize, stride: usize) -> Self {
+        let index_size = if vertices > u16::MAX as usize { 4 } else { 2 };
+        Self { triangles: indices.len() / 3, misses: misses(indices), vertices, bytes: vertices * stride + indices.len() * index_size }
+    }
+    /// Average cache miss ratio, transformed vertices per triangle.
+    pub fn acmr(&self) -> f32 {
+        if self.triangles == 0 { 0. } else { self.misses as f32 / self.triangles as f32 }
+    }
+    pub fn add(&mut self, other: Stats) {
+        self.triangles += other.triangles;
+        self.misses += other.misses;
+        self.vertices += other.vertices;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Optimizes a triangle list whose `indices` point into `vertices`, `stride` bytes per vertex:
+/// reorders triangles for the vertex cache, then clusters of them to reduce overdraw,
+/// then the vertices in the order they are first used. Returns the new index of every vertex.
+pub fn optimize(indices: &mut [u32], vertices: &mut Vec<u8>, positions: &[[f32;3]], stride: usize) -> Vec<u32> {
+    vertex_cache(indices, positions.len());
+    overdraw(indices, positions);
+    vertex_fetch(indices, vertices, stride)
+}
+
+fn misses(indices: &[u32]) -> usize {
+    let mut cache = VecDeque::with_capacity(FIFO_SIZE);
+    let mut res = 0;
+    for i in indices {
+        if cache.contains(i) { continue }
+        if cache.len() == FIFO_SIZE { cache.pop_front(); }
+        cache.push_back(*i);
+        res += 1;
+    }
+    res
+}
+
+/// Tom Forsyth's linear-speed vertex cache optimization: greedily emits the triangle with the best score,
+/// vertices score higher when recently used and when few of their triangles are left.
+pub fn vertex_cache(indices: &mut [u32], vertices: usize) {
+    let triangles = indices.len() / 3;
+    let mut offsets = vec![0; vertices + 1];
+    for i in indices.iter() {
+        offsets[*i as usize + 1] += 1;
+    }
+    for v in 0..vertices {
+        offsets[v + 1] += offsets[v];
+    }
+    let mut adjacency = vec![0; indices.len()];
+    let mut fill = offsets.clone();
+    for (id, i) in indices.iter().enumerate() {
+        adjacency[fill[*i as usize]] = id / 3;
+        fill[*i as usize] += 1;
+    }
+    let score = |cache_position: Option<usize>, remaining: usize| -> f32 {
+        if remaining == 0 { return -1. }
+        let cache = match cache_position {
+            None => 0.,
+            // The last triangle is scored flat so its order does not matter
+            Some(v) if v < 3 => 0.75,
+            Some(v) => (1. - (v - 3) as f32 / (LRU_SIZE - 3) as f32).powf(1.5)
+        };
+        cache + 2. / (remaining as f32).sqrt()
+    };
+
+    let mut remaining: Vec<usize> = (0..vertices).map(|v| offsets[v + 1] - offsets[v]).collect();
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertices];
+    let mut vertex_score: Vec<f32> = remaining.iter().map(|v| score(None, *v)).collect();
+    let mut triangle_score: Vec<f32> = indices.chunks(3).map(|t| t.iter().map(|v| vertex_score[*v as usize]).sum()).collect();
+    let mut emitted = vec![false; triangles];
+    let mut cache: Vec<u32> = Vec::with_capacity(LRU_SIZE + 3);
+    let mut res = Vec::with_capacity(indices.len());
+    let mut next_unemitted = 0;
+    let mut best = (0..triangles).max_by(|a, b| triangle_score[*a].total_cmp(&triangle_score[*b]));
+    while let Some(triangle) = best {
+        emitted[triangle] = true;
+        let corners = [indices[triangle * 3], indices[triangle * 3 + 1], indices[triangle * 3 + 2]];
+        res.extend_from_slice(&corners);
+        for v in corners {
+            remaining[v as usize] -= 1;
+        }
+        let mut new_cache = corners.to_vec();
+        new_cache.extend(cache.iter().filter(|v| !corners.contains(v)));
+        for v in new_cache.iter().skip(LRU_SIZE) {
+            cache_position[*v as usize] = None;
+        }
+        for (position, v) in new_cache.iter().enumerate() {
+            if position < LRU_SIZE { cache_position[*v as usize] = Some(position) }
+            vertex_score[*v as usize] = score(cache_position[*v as usize], remaining[*v as usize]);
+        }
+        best = None;
+        let mut best_score = f32::MIN;
+        for v in &new_cache {
+            for t in &adjacency[offsets[*v as usize]..offsets[*v as usize + 1]] {
+                if emitted[*t] { continue }
+                triangle_score[*t] = indices[t * 3..t * 3 + 3].iter().map(|v| vertex_score[*v as usize]).sum();
+                if triangle_score[*t] > best_score {
+                    best_score = triangle_score[*t];
+                    best = Some(*t);
+                }
+            }
+        }
+        new_cache.truncate(LRU_SIZE);
+        cache = new_cache;
+        if best.is_none() {
+            while next_unemitted < triangles && emitted[next_unemitted] {
+                next_unemitted += 1;
+            }
+            best = Some(next_unemitted).filter(|v| *v < triangles);
+        }
+    }
+    indices[..res.len()].copy_from_slice(&res);
+}
+
+/// Splits the triangles where the cache restarts, every vertex of the triangle being a miss, and draws
+/// the clusters facing away from the mesh center first so they occlude the rest. Only whole clusters
+/// move, which keeps the cache order within them.
+pub fn overdraw(indices: &mut [u32], positions: &[[f32;3]]) {
+    let position = |i: u32| Vector3::from(positions[i as usize]);
+    let starts = cluster_starts(indices);
+    if starts.len() < 2 { return }
+
+    let mut center = Vector3::new(0., 0., 0.);
+    let mut area = 0.;
+    let mut clusters = Vec::with_capacity(starts.len());
+    for (id, start) in starts.iter().enumerate() {
+        let end = starts.get(id + 1).copied().unwrap_or(indices.len());
+        let (mut cluster_center, mut normal, mut cluster_area) = (Vector3::new(0., 0., 0.), Vector3::new(0., 0., 0.), 0.);
+        for corners in indices[*start..end].chunks(3) {
+            let (a, b, c) = (position(corners[0]), position(corners[1]), position(corners[2]));
+            let cross = (b - a).cross(c - a);
+            let triangle_area = cross.magnitude() * 0.5;
+            cluster_center += (a + b + c) / 3. * triangle_area;
+            cluster_area += triangle_area;
+            normal += cross;
+        }
+        center += cluster_center;
+        area += cluster_area;
+        if cluster_area > 0. { cluster_center /= cluster_area }
+        clusters.push((*start..end, cluster_center, normal));
+    }
+    if area > 0. { center /= area }
+    let key = |v: &(std::ops::Range<usize>, Vector3<f32>, Vector3<f32>)| {
+        if v.2.magnitude2() > 0. { (v.1 - center).dot(v.2.normalize()) } else { 0. }
+    };
+    clusters.sort_by(|a, b| key(b).total_cmp(&key(a)));
+    let res: Vec<u32> = clusters.iter().flat_map(|v| indices[v.0.clone()].to_vec()).collect();
+    indices.copy_from_slice(&res);
+}
+
+/// First index of every cluster, a cluster starts with a triangle whose vertices all miss the cache.
+fn cluster_starts(indices: &[u32]) -> Vec<usize> {
+    let mut cache = VecDeque::with_capacity(FIFO_SIZE);
+    let mut res = Vec::new();
+    for (triangle, corners) in indices.chunks(3).enumerate() {
+        let mut misses = 0;
+        for i in corners {
+            if cache.contains(i) { continue }
+            if cache.len() == FIFO_SIZE { cache.pop_front(); }
+            cache.push_back(*i);
+            misses += 1;
+        }
+        if misses == 3 { res.push(triangle * 3) }
+    }
+    res
+}
+
+/// Renumbers the vertices in the order the triangles use them, unused vertices are removed and mapped to `u32::MAX`.
+pub fn vertex_fetch(indices: &mut [u32], vertices: &mut Vec<u8>, stride: usize) -> Vec<u32> {
+    let mut remap = vec![u32::MAX; vertices.len() / stride];
+    let mut res = Vec::with_capacity(vertices.len());
+    for i in indices.iter_mut() {
+        if remap[*i as usize] == u32::MAX {
+            remap[*i as usize] = (res.len() / stride) as u32;
+            res.extend_from_slice(&vertices[*i as usize * stride..(*i as usize + 1) * stride]);
+        }
+        *i = remap[*i as usize];
+    }
+    *vertices = res;
+    remap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Triangles of a `size` by `size` quad grid wrapped around a sphere, row by row.
+    fn grid(size: u32) -> (Vec<u32>, Vec<[f32;3]>) {
+        let mut positions = Vec::new();
+        for y in 0..=size {
+            for x in 0..=size {
+                let (u, v) = (x as f32 / size as f32 * std::f32::consts::TAU, y as f32 / size as f32 * std::f32::consts::PI);
+                positions.push([u.cos() * v.sin(), v.cos(), u.sin() * v.sin()]);
+            }
+        }
+        let mut indices = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let i = y * (size + 1) + x;
+                indices.extend_from_slice(&[i, i + size + 1, i + 1, i + 1, i + size + 1, i + size + 2]);
+            }
+        }
+        (indices, positions)
+    }
+    /// Same triangles in a deterministic random order.
+    fn shuffled(indices: &[u32]) -> Vec<u32> {
+        let mut triangles: Vec<&[u32]> = indices.chunks(3).collect();
+        let mut state = 0x9e3779b9u32;
+        for i in (1..triangles.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            triangles.swap(i, state as usize % (i + 1));
+        }
+        triangles.concat()
+    }
+    /// Triangles rotated to start with their smallest index, which keeps the winding, and sorted.
+    fn triangles(indices: &[u32]) -> Vec<[u32;3]> {
+        let mut res: Vec<[u32;3]> = indices.chunks(3).map(|t| {
+            let first = (0..3).min_by_key(|v| t[*v]).unwrap();
+            [t[first], t[(first + 1) % 3], t[(first + 2) % 3]]
+        }).collect();
+        res.sort();
+        res
+    }
+
+    #[test]
+    fn vertex_cache_does_not_raise_acmr() {
+        let (rows, positions) = grid(24);
+        for indices in [rows.clone(), shuffled(&rows)] {
+            let mut optimized = indices.clone();
+            vertex_cache(&mut optimized, positions.len());
+            let (before, after) = (Stats::new(&indices, positions.len(), 12), Stats::new(&optimized, positions.len(), 12));
+            assert!(after.acmr() <= before.acmr(), "{} -> {}", before.acmr(), after.acmr());
+            assert!(after.acmr() < 1., "{}", after.acmr());
+            assert_eq!(triangles(&optimized), triangles(&indices));
+        }
+    }
+
+    #[test]
+    fn vertex_fetch_keeps_the_triangles() {
+        let (indices, positions) = grid(8);
+        // One unused vertex at the end
+        let vertices = positions.len() + 1;
+        let mut indices = shuffled(&indices);
+        let original = indices.clone();
+        let mut data: Vec<u8> = (0..vertices as u32).flat_map(|v| v.to_le_bytes()).collect();
+        let remap = vertex_fetch(&mut indices, &mut data, 4);
+        assert_eq!(remap.len(), vertices);
+        assert_eq!(remap[vertices - 1], u32::MAX);
+        let mut used: Vec<u32> = remap[..vertices - 1].to_vec();
+        used.sort();
+        assert_eq!(used, (0..vertices as u32 - 1).collect::<Vec<_>>());
+        assert_eq!(data.len(), (vertices - 1) * 4);
+        // Every new vertex holds the data of the one it replaces and the triangles read the same data
+        for (old, new) in remap.iter().enumerate().filter(|v| *v.1 != u32::MAX) {
+            assert_eq!(data[*new as usize * 4..*new as usize * 4 + 4], (old as u32).to_le_bytes());
+        }
+        let read: Vec<u32> = indices.iter().map(|i| u32::from_le_bytes(data[*i as usize * 4..*i as usize * 4 + 4].try_into().unwrap())).collect();
+        assert_eq!(read, original);
+        // Vertices are numbered in the order they are first used
+        let mut next = 0;
+        for i in &indices {
+            assert!(*i <= next);
+            if *i == next { next += 1 }
+        }
+    }
+
+    #[test]
+    fn overdraw_moves_whole_clusters() {
+        let (indices, positions) = grid(16);
+        let indices = shuffled(&indices);
+        let starts = cluster_starts(&indices);
+        assert!(starts.len() > 2);
+        let mut clusters: Vec<&[u32]> = starts.iter().enumerate()
+            .map(|(id, start)| &indices[*start..starts.get(id + 1).copied().unwrap_or(indices.len())]).collect();
+        let mut sorted = indices.clone();
+        overdraw(&mut sorted, &positions);
+        assert_ne!(sorted, indices);
+        // The output is the input clusters, each one whole and used once
+        let mut position = 0;
+        while position < sorted.len() {
+            let found = clusters.iter().position(|v| sorted[position..].starts_with(v)).expect("no cluster at this position");
+            position += clusters.swap_remove(found).len();
+        }
+        assert!(clusters.is_empty());
+    }
+}
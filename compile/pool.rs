@@ -0,0 +1,55 @@
+use std::{path::PathBuf, panic::{self, AssertUnwindSafe}, sync::{Mutex, Once}, collections::VecDeque, cell::Cell};
+
+pub type Job = Box<dyn FnOnce() -> Result<(), String> + Send>;
+
+thread_local! {
+    /// Set on worker threads, whose panics are reported as failed jobs instead of printed.
+    static WORKER: Cell<bool> = const { Cell::new(false) };
+}
+static QUIET_WORKERS: Once = Once::new();
+
+/// Runs every job on `workers` threads and waits for all of them. Returns the source
+/// path and error of each failed job, panics inside a job count as failures.
+pub fn run(jobs: Vec<(PathBuf, Job)>, workers: usize) -> Vec<(PathBuf, String)> {
+    let queue = Mutex::new(VecDeque::from(jobs));
+    let failures = Mutex::new(Vec::new());
+    // Wraps the current hook once instead of swapping it, other threads keep printing their panics
+    QUIET_WORKERS.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| if !WORKER.with(|v| v.get()) { hook(info) }));
+    });
+    std::thread::scope(|s| {
+        for _ in 0..workers.max(1) {
+            s.spawn(|| {
+                WORKER.with(|v| v.set(true));
+                loop {
+                    let (path, job) = match queue.lock().unwrap().pop_front() {
+                        Some(v) => v,
+                        None => break
+                    };
+                    let res = match panic::catch_unwind(AssertUnwindSafe(job)) {
+                        Ok(v) => v,
+                        Err(e) => Err(panic_message(e))
+                    };
+                    if let Err(e) = res {
+                        println!("Error: {}, {}", path.display(), e);
+                        failures.lock().unwrap().push((path, e));
+                    }
+                }
+            });
+        }
+    });
+    let mut failures = failures.into_inner().unwrap();
+    failures.sort();
+    failures
+}
+
+fn panic_message(e: Box<dyn std::any::Any + Send>) -> String {
+    match e.downcast::<String>() {
+        Ok(v) => *v,
+        Err(e) => match e.downcast::<&str>() {
+            Ok(v) => v.to_string(),
+            Err(_) => "panicked".to_string()
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use crate::{Config, low::{self, Kind, Section}, keyframes::Trs, nodes::{Camera, Light, LightKind, Node}};
+
+/// Writes the node tree of the default glTF scene to an `S` file: local transforms, the submeshes
+/// of `mesh_output` each node draws, skins, cameras and `KHR_lights_punctual` lights.
+pub fn write(path: &Path, conf: &Config, gltf: &gltf::Document, mesh_output: &Path, output: &Path) -> Result<(), String> {
+    let scene = gltf.default_scene().or_else(|| gltf.scenes().next()).ok_or("File has no scene")?;
+    // Primitives are compiled as submeshes in order
+    let mut first_submesh = 0;
+    let submeshes: Vec<_> = gltf.meshes().map(|mesh| {
+        let range = first_submesh..first_submesh + mesh.primitives().len();
+        first_submesh = range.end;
+        range
+    }).collect();
+
+    let mut nodes = Vec::new();
+    // Depth first, children are pushed in reverse to pop them in order
+    let mut stack: Vec<(gltf::Node, Option<usize>)> = scene.nodes().map(|v| (v, None)).collect();
+    stack.reverse();
+    while let Some((node, parent)) = stack.pop() {
+        let (translation, rotation, scale) = node.transform().decomposed();
+        nodes.push(Node {
+            name: node.name().unwrap_or("").to_string(),
+            parent,
+            local: conf.convert_trs(Trs { translation, rotation, scale }),
+            submeshes: node.mesh().map_or(0..0, |v| submeshes[v.index()].clone()),
+            // Meshes without joints were bound to the skin with the node transform baked in, see `rigid_bind`
+            skin: node.skin().or_else(|| gltf.skins().next().filter(|_| node.mesh().is_some() && conf.vertex_type.skinned())).map(|v| v.index()),
+            camera: node.camera().map(|v| v.index()),
+            light: node.light().map(|v| v.index())
+        });
+        let id = nodes.len() - 1;
+        let first_child = stack.len();
+        stack.extend(node.children().map(|v| (v, Some(id))));
+        stack[first_child..].reverse();
+    }
+    if nodes.len() >= u16::MAX as usize { return Err(format!("Scenes can not have more than {} nodes", u16::MAX - 1)) }
+
+    let mut b = Vec::new();
+    low::append_str(&mut b, &mesh_output.to_string_lossy());
+    b.extend_from_slice(&(nodes.len() as u16).to_be_bytes());
+    for node in &nodes {
+        node.write(&mut b);
+    }
+    let mut f = low::Writer::new(Kind::Scene);
+    f.metadata(&[("source", &path.to_string_lossy())]);
+    f.section(Section::Nodes, b);
+
+    let lights: Vec<Light> = gltf.lights().into_iter().flatten().map(|light| {
+        let (kind, inner_cone, outer_cone) = match light.kind() {
+            gltf::khr_lights_punctual::Kind::Directional => (LightKind::Directional, 0., 0.),
+            gltf::khr_lights_punctual::Kind::Point => (LightKind::Point, 0., 0.),
+            gltf::khr_lights_punctual::Kind::Spot { inner_cone_angle, outer_cone_angle } => (LightKind::Spot, inner_cone_angle, outer_cone_angle)
+        };
+        Light {
+            name: light.name().unwrap_or("").to_string(),
+            kind,
+            color: light.color(),
+            intensity: light.intensity(),
+            range: light.range().map(|v| v * conf.scale),
+            inner_cone,
+            outer_cone
+        }
+    }).collect();
+    let mut b = (lights.len() as u16).to_be_bytes().to_vec();
+    for light in &lights {
+        light.write(&mut b);
+    }
+    f.section(Section::Lights, b);
+
+    let mut b = (gltf.cameras().len() as u16).to_be_bytes().to_vec();
+    for camera in gltf.cameras() {
+        match camera.projection() {
+            gltf::camera::Projection::Perspective(v) => Camera::Perspective {
+                yfov: v.yfov(),
+                aspect: v.aspect_ratio(),
+                znear: v.znear() * conf.scale,
+                zfar: v.zfar().map(|v| v * conf.scale)
+            },
+            gltf::camera::Projection::Orthographic(v) => Camera::Orthographic {
+                xmag: v.xmag() * conf.scale,
+                ymag: v.ymag() * conf.scale,
+                znear: v.znear() * conf.scale,
+                zfar: v.zfar() * conf.scale
+            }
+        }.write(&mut b);
+    }
+    f.section(Section::Cameras, b);
+    f.save(output).map_err(|e| e.to_string())
+}
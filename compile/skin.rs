@@ -0,0 +1,147 @@
+/// Joints and weights of every vertex, from one `JOINTS_n` and `WEIGHTS_n` pair.
+pub type Influences = (Vec<[u16;4]>, Vec<[f32;4]>);
+
+/// Counts of the vertices the cleanup changed or could not fix.
+#[derive(Default)]
+pub struct Stats {
+    /// Weights not summing to 1 in the source.
+    pub renormalized: usize,
+    /// Influences below the minimum weight.
+    pub pruned: usize,
+    /// Vertices with more than 4 influences.
+    pub reduced: usize,
+    /// Vertices without any weight, bound to joint 0.
+    pub zero: usize,
+    /// Vertices with a weighted joint outside of the skin, the influence is dropped.
+    pub outside: usize
+}
+impl Stats {
+    pub fn add(&mut self, other: Stats) {
+        self.renormalized += other.renormalized;
+        self.pruned += other.pruned;
+        self.reduced += other.reduced;
+        self.zero += other.zero;
+        self.outside += other.outside;
+    }
+}
+
+/// Merges the influences of every joint set of a vertex, keeps the 4 largest above `min_weight`
+/// and normalizes them to sum to 1. Influences are sorted by weight, unused slots are joint 0 with weight 0
+/// which the skinning shaders skip.
+pub fn clean(sets: &[Influences], joints_len: usize, min_weight: f32) -> (Influences, Stats) {
+    let vertices = sets.iter().map(|v| v.0.len().min(v.1.len())).min().unwrap_or(0);
+    let (mut joints, mut weights) = (Vec::with_capacity(vertices), Vec::with_capacity(vertices));
+    let mut stats = Stats::default();
+    let mut influences: Vec<(u16, f32)> = Vec::new();
+    for i in 0..vertices {
+        influences.clear();
+        let mut outside = false;
+        for (j, w) in sets.iter().flat_map(|(j, w)| j[i].into_iter().zip(w[i])) {
+            if !w.is_finite() || w <= 0. { continue }
+            if j as usize >= joints_len {
+                outside = true;
+                continue
+            }
+            match influences.iter_mut().find(|v| v.0 == j) {
+                Some(v) => v.1 += w,
+                None => influences.push((j, w))
+            }
+        }
+        if outside { stats.outside += 1 }
+        let total: f32 = influences.iter().map(|v| v.1).sum();
+        if total == 0. {
+            stats.zero += 1;
+            joints.push([0;4]);
+            weights.push([1., 0., 0., 0.]);
+            continue
+        }
+        if (total - 1.).abs() > 1e-3 { stats.renormalized += 1 }
+        influences.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        if influences.len() > 4 {
+            stats.reduced += 1;
+            influences.truncate(4);
+        }
+        // The largest influence is always kept
+        let (length, largest) = (influences.len(), influences[0].1);
+        influences.retain(|v| v.1 == largest || v.1 / total >= min_weight);
+        stats.pruned += length - influences.len();
+        let total: f32 = influences.iter().map(|v| v.1).sum();
+        let (mut j, mut w) = ([0;4], [0.;4]);
+        for (k, (joint, weight)) in influences.iter().enumerate() {
+            j[k] = *joint;
+            w[k] = weight / total;
+        }
+        joints.push(j);
+        weights.push(w);
+    }
+    ((joints, weights), stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One vertex from one joint set.
+    fn vertex(joints: [u16;4], weights: [f32;4]) -> Influences {
+        (vec![joints], vec![weights])
+    }
+    fn assert_weights(a: [f32;4], b: [f32;4]) {
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-6), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn renormalizes() {
+        let ((joints, weights), stats) = clean(&[vertex([0, 1, 2, 3], [0.5;4])], 4, 0.01);
+        assert_eq!(joints[0], [0, 1, 2, 3]);
+        assert_weights(weights[0], [0.25;4]);
+        assert_eq!((stats.renormalized, stats.pruned, stats.reduced), (1, 0, 0));
+
+        // Weights already summing to 1 are not counted
+        let (_, stats) = clean(&[vertex([0, 1, 0, 0], [0.6, 0.4, 0., 0.])], 4, 0.01);
+        assert_eq!(stats.renormalized, 0);
+    }
+
+    #[test]
+    fn prunes_small_weights() {
+        let ((joints, weights), stats) = clean(&[vertex([2, 1, 0, 0], [0.995, 0.005, 0., 0.])], 3, 0.01);
+        assert_eq!(joints[0], [2, 0, 0, 0]);
+        assert_weights(weights[0], [1., 0., 0., 0.]);
+        assert_eq!(stats.pruned, 1);
+
+        // The largest influence stays when every weight is below the minimum
+        let ((joints, weights), _) = clean(&[vertex([0, 1, 2, 3], [0.2, 0.4, 0.3, 0.1])], 4, 0.5);
+        assert_eq!(joints[0], [1, 0, 0, 0]);
+        assert_weights(weights[0], [1., 0., 0., 0.]);
+    }
+
+    #[test]
+    fn keeps_largest_four() {
+        // Joint 1 and 0 are in both sets, their weights add up, the tie of joint 0 and 5 keeps the lower joint
+        let sets = [vertex([0, 1, 2, 3], [0.1, 0.3, 0.05, 0.15]), vertex([4, 5, 1, 0], [0.2, 0.1, 0.1, 0.])];
+        let ((joints, weights), stats) = clean(&sets, 6, 0.01);
+        assert_eq!(joints[0], [1, 4, 3, 0]);
+        assert_weights(weights[0], [0.4 / 0.85, 0.2 / 0.85, 0.15 / 0.85, 0.1 / 0.85]);
+        assert_eq!((stats.reduced, stats.renormalized), (1, 0));
+    }
+
+    #[test]
+    fn drops_joints_outside_of_skin() {
+        let ((joints, weights), stats) = clean(&[vertex([1, 9, 0, 0], [0.5, 0.5, 0., 0.])], 2, 0.01);
+        assert_eq!(joints[0], [1, 0, 0, 0]);
+        assert_weights(weights[0], [1., 0., 0., 0.]);
+        assert_eq!((stats.outside, stats.renormalized), (1, 1));
+    }
+
+    #[test]
+    fn binds_unweighted_vertices_to_first_joint() {
+        for w in [[0.;4], [f32::NAN, -1., 0., 0.]] {
+            let ((joints, weights), stats) = clean(&[vertex([3, 2, 1, 0], w)], 4, 0.01);
+            assert_eq!(joints[0], [0;4]);
+            assert_eq!(weights[0], [1., 0., 0., 0.]);
+            assert_eq!(stats.zero, 1);
+        }
+        // Also when the only weighted joint is outside of the skin
+        let (_, stats) = clean(&[vertex([5, 0, 0, 0], [1., 0., 0., 0.])], 4, 0.01);
+        assert_eq!((stats.zero, stats.outside), (1, 1));
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use cgmath::{InnerSpace, Vector2, Vector3};
+
+/// Tangent of every corner of a triangle list with the handedness in w, following MikkTSpace:
+/// face tangents from the UV gradients are projected on the corner normal and angle weighted,
+/// corners only share a tangent when their position, normal, UV and handedness are the same.
+/// Corners of triangles without UV area get a tangent perpendicular to the normal.
+pub fn generate(positions: &[[f32;3]], normals: &[[f32;3]], uvs: &[[f32;2]], indices: &[u32]) -> Vec<[f32;4]> {
+    let mut corners: Vec<Option<(Vector3<f32>, bool)>> = vec![None; indices.len()];
+    let mut sums: HashMap<([u32;8], bool), Vector3<f32>> = HashMap::new();
+    let key = |i: u32, negative: bool| {
+        let (p, n, uv) = (positions[i as usize], normals[i as usize], uvs[i as usize]);
+        ([p[0], p[1], p[2], n[0], n[1], n[2], uv[0], uv[1]].map(f32::to_bits), negative)
+    };
+    for (triangle, t) in indices.chunks_exact(3).enumerate() {
+        let p = t.iter().map(|i| Vector3::from(positions[*i as usize])).collect::<Vec<_>>();
+        let uv = t.iter().map(|i| Vector2::from(uvs[*i as usize])).collect::<Vec<_>>();
+        let (e1, e2) = (p[1] - p[0], p[2] - p[0]);
+        let (d1, d2) = (uv[1] - uv[0], uv[2] - uv[0]);
+        let area = d1.x * d2.y - d2.x * d1.y;
+        if area.abs() <= f32::EPSILON { continue }
+        let tangent = (e1 * d2.y - e2 * d1.y) / area;
+        // glTF UVs start at the top of the image, the +Y of normal maps points to decreasing v
+        let bitangent = (e1 * d2.x - e2 * d1.x) / area;
+        for k in 0..3 {
+            let n = Vector3::from(normals[t[k] as usize]);
+            let projected = tangent - n * n.dot(tangent);
+            if projected.magnitude2() <= f32::EPSILON * f32::EPSILON { continue }
+            let (a, b) = (p[(k + 1) % 3] - p[k], p[(k + 2) % 3] - p[k]);
+            let angle = if a.magnitude2() > 0. && b.magnitude2() > 0. { a.normalize().dot(b.normalize()).clamp(-1., 1.).acos() } else { 0. };
+            let negative = n.cross(tangent).dot(bitangent) < 0.;
+            *sums.entry(key(t[k], negative)).or_insert(Vector3::new(0., 0., 0.)) += projected.normalize() * angle;
+            corners[triangle * 3 + k] = Some((projected, negative));
+        }
+    }
+    indices.iter().zip(corners).map(|(i, corner)| {
+        let n = Vector3::from(normals[*i as usize]);
+        match corner {
+            Some((projected, negative)) => {
+                let sum = sums[&key(*i, negative)];
+                let t = if sum.magnitude2() > 0. { sum.normalize() } else { projected.normalize() };
+                [t.x, t.y, t.z, if negative { -1. } else { 1. }]
+            }
+            None => perpendicular(n)
+        }
+    }).collect()
+}
+
+/// Any tangent of the normal plane, for vertices without usable UVs.
+pub fn perpendicular(n: Vector3<f32>) -> [f32;4] {
+    let axis = if n.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+    let t = (axis - n * n.dot(axis)).normalize();
+    [t.x, t.y, t.z, 1.]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Quads of a strip along x facing +z, the UVs of quad `k` run along x or against it when `mirrored[k]`.
+    /// Neighbour quads share the vertices of their edge.
+    fn strip(mirrored: &[bool]) -> (Vec<[f32;3]>, Vec<[f32;3]>, Vec<[f32;2]>, Vec<u32>) {
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        // The UV of a shared edge is the same on both sides, the mirroring only flips the direction
+        let mut u = 0.;
+        for x in 0..=mirrored.len() {
+            positions.extend_from_slice(&[[x as f32, 0., 0.], [x as f32, 1., 0.]]);
+            uvs.extend_from_slice(&[[u, 1.], [u, 0.]]);
+            if let Some(m) = mirrored.get(x) { u += if *m { -1. } else { 1. } }
+        }
+        let indices = (0..mirrored.len() as u32).flat_map(|k| [0, 2, 3, 0, 3, 1].map(|v| k * 2 + v)).collect();
+        (positions.clone(), vec![[0., 0., 1.]; positions.len()], uvs, indices)
+    }
+
+    #[test]
+    fn follows_uv_direction() {
+        let (positions, normals, uvs, indices) = strip(&[false]);
+        for t in generate(&positions, &normals, &uvs, &indices) {
+            assert_eq!(t, [1., 0., 0., 1.]);
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_flip_handedness() {
+        let (positions, normals, uvs, indices) = strip(&[true]);
+        for t in generate(&positions, &normals, &uvs, &indices) {
+            assert_eq!(t, [-1., 0., 0., -1.]);
+        }
+    }
+
+    #[test]
+    fn mirror_seam_keeps_both_sides() {
+        // The vertices of the middle edge have the same position, normal and UV on both sides,
+        // only the handedness keeps their corners from being averaged into one tangent
+        let (positions, normals, uvs, indices) = strip(&[false, true]);
+        let tangents = generate(&positions, &normals, &uvs, &indices);
+        for (corner, t) in tangents.iter().enumerate() {
+            assert_eq!(*t, if corner < 6 { [1., 0., 0., 1.] } else { [-1., 0., 0., -1.] });
+        }
+    }
+
+    #[test]
+    fn degenerate_uvs_are_perpendicular() {
+        let (positions, normals, _, indices) = strip(&[false]);
+        for t in generate(&positions, &normals, &[[0.5, 0.5]; 4], &indices) {
+            assert_eq!(t, [1., 0., 0., 1.]);
+        }
+        let n = Vector3::new(1., 0., 0.);
+        let t = perpendicular(n);
+        assert!(n.dot(Vector3::new(t[0], t[1], t[2])).abs() < 1e-6);
+    }
+}
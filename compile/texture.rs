@@ -0,0 +1,174 @@
+use std::{path::Path, fs, sync::{Arc, Mutex}};
+
+use crate::{Config, Dirs, bc, cache::{self, Cache}, low::{self, Kind, Section, TextureCompression, TextureKind}};
+
+struct Level {
+    width: u32,
+    height: u32,
+    /// Linear values, `channels` floats per pixel. RGBA color is premultiplied by alpha.
+    data: Vec<f32>
+}
+
+pub fn image(path: impl AsRef<Path>, dirs: &Dirs, conf: Config, cache: Arc<Mutex<Cache>>) -> Result<(), String> {
+    let conf = conf.file(path.as_ref())?;
+    if path.as_ref().file_name().unwrap().to_string_lossy().starts_with('_') { return Ok(()) }
+    let output_path = dirs.compiled.join(path.as_ref().strip_prefix(&dirs.assets).unwrap()).with_extension("low");
+    let entry = cache::Entry::new(&path, &[path.as_ref().to_path_buf()], format!("{:?}", conf));
+    if cache.lock().unwrap().check(&output_path, &entry) { return Ok(()) }
+    println!("OutputPath: {}, {:?}", output_path.display(), conf);
+    fs::create_dir_all(output_path.parent().unwrap()).map_err(|e| e.to_string())?;
+    let mut f = low::Writer::new(Kind::Image);
+    f.metadata(&[("source", &path.as_ref().to_string_lossy())]);
+
+    let data = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let image = image::load_from_memory(&data).map_err(|e| e.to_string())?;
+    let (width, height) = (image.width(), image.height());
+    let kind = conf.texture_kind;
+    let mut levels = match kind {
+        TextureKind::Color | TextureKind::Linear => mip_chain(width, height, image.to_rgba8().as_raw(), kind),
+        TextureKind::Normal => mip_chain(width, height, image.to_rgb8().as_raw(), kind),
+        TextureKind::Mask => mip_chain(width, height, image.to_luma8().as_raw(), kind)
+    };
+    if !conf.mipmaps { levels.truncate(1) }
+
+    let compression = match conf.texture_compression {
+        TextureCompression::Bc if width % 4 != 0 || height % 4 != 0 => {
+            println!("Warning: {} is {}x{}, block compression needs a multiple of 4, stored uncompressed", path.as_ref().display(), width, height);
+            TextureCompression::None
+        }
+        v => v
+    };
+    let levels = match compression {
+        TextureCompression::None => levels,
+        TextureCompression::Bc => levels.iter().enumerate()
+            .map(|(i, level)| bc::encode(kind, (width >> i).max(1), (height >> i).max(1), level))
+            .collect()
+    };
+
+    let mut b = Vec::with_capacity(11 + levels[0].len() * 4 / 3);
+    b.extend_from_slice(&width.to_be_bytes());
+    b.extend_from_slice(&height.to_be_bytes());
+    b.push(kind.id());
+    b.push(compression.id());
+    b.push(levels.len() as u8);
+    for level in &levels {
+        b.extend_from_slice(level);
+    }
+    f.section(Section::Pixels, b);
+    f.save(&output_path).map_err(|e| e.to_string())?;
+    cache.lock().unwrap().insert(&output_path, entry);
+    Ok(())
+}
+
+/// Every mip level down to 1x1 in the stored `kind` layout. Levels are filtered in linear space
+/// from the previous unquantized level, color is decoded from sRGB and normals are renormalized.
+/// RGBA is filtered premultiplied so transparent pixels do not bleed their color into the level.
+/// `data` is RGB for normal maps and `kind.channels()` bytes per pixel otherwise.
+pub fn mip_chain(width: u32, height: u32, data: &[u8], kind: TextureKind) -> Vec<Vec<u8>> {
+    let channels = if kind == TextureKind::Normal { 3 } else { kind.channels() };
+    let alpha = matches!(kind, TextureKind::Color | TextureKind::Linear);
+    let mut level = Level {
+        width, height,
+        data: data.iter().enumerate().map(|(i, v)| match kind {
+            TextureKind::Color if i % channels < 3 => srgb_to_linear(*v),
+            TextureKind::Normal => *v as f32 / 255. * 2. - 1.,
+            _ => *v as f32 / 255.
+        }).collect()
+    };
+    if alpha {
+        for pixel in level.data.chunks_mut(4) {
+            for c in 0..3 {
+                pixel[c] *= pixel[3];
+            }
+        }
+    }
+    let encode = |level: &Level| -> Vec<u8> {
+        let mut res = Vec::with_capacity((level.width * level.height) as usize * kind.channels());
+        for pixel in level.data.chunks(channels) {
+            match kind {
+                TextureKind::Normal => {
+                    let length = pixel.iter().map(|v| v * v).sum::<f32>().sqrt().max(f32::EPSILON);
+                    res.extend(pixel[..2].iter().map(|v| ((v / length * 0.5 + 0.5).clamp(0., 1.) * 255.).round() as u8));
+                }
+                _ => res.extend(pixel.iter().enumerate().map(|(i, v)| {
+                    let v = if alpha && i < 3 && pixel[3] > 0. { v / pixel[3] } else { *v };
+                    if kind == TextureKind::Color && i < 3 { linear_to_srgb(v) } else { (v.clamp(0., 1.) * 255.).round() as u8 }
+                }))
+            }
+        }
+        res
+    };
+    // The source level keeps the color of transparent pixels
+    let mut levels = vec![if alpha { data.to_vec() } else { encode(&level) }];
+    while level.width > 1 || level.height > 1 {
+        level = downsample(&level, channels);
+        levels.push(encode(&level));
+    }
+    levels
+}
+
+/// Halves the level with a separable [1, 3, 3, 1] tent filter, edges are clamped.
+fn downsample(level: &Level, channels: usize) -> Level {
+    const WEIGHTS: [f32;4] = [0.125, 0.375, 0.375, 0.125];
+    let (width, height) = ((level.width / 2).max(1), (level.height / 2).max(1));
+    let tap = |i: u32, k: u32, size: u32, halved: bool| -> u32 {
+        if !halved { return i }
+        (2 * i + k).saturating_sub(1).min(size - 1)
+    };
+    let mut horizontal = vec![0.; (width * level.height) as usize * channels];
+    for y in 0..level.height {
+        for x in 0..width {
+            for (k, w) in WEIGHTS.iter().enumerate() {
+                let sx = tap(x, k as u32, level.width, level.width > 1);
+                let src = ((y * level.width + sx) as usize) * channels;
+                let dst = ((y * width + x) as usize) * channels;
+                for c in 0..channels {
+                    horizontal[dst + c] += w * level.data[src + c];
+                }
+            }
+        }
+    }
+    let mut data = vec![0.; (width * height) as usize * channels];
+    for y in 0..height {
+        for (k, w) in WEIGHTS.iter().enumerate() {
+            let sy = tap(y, k as u32, level.height, level.height > 1);
+            for x in 0..width {
+                let src = ((sy * width + x) as usize) * channels;
+                let dst = ((y * width + x) as usize) * channels;
+                for c in 0..channels {
+                    data[dst + c] += w * horizontal[src + c];
+                }
+            }
+        }
+    }
+    Level { width, height, data }
+}
+
+fn srgb_to_linear(v: u8) -> f32 {
+    let v = v as f32 / 255.;
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+fn linear_to_srgb(v: f32) -> u8 {
+    let v = v.clamp(0., 1.);
+    let v = if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1. / 2.4) - 0.055 };
+    (v * 255.).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transparent_pixels_do_not_bleed() {
+        // One opaque red pixel among transparent green ones stays red, with a quarter of the coverage
+        let data = [[255, 0, 0, 255], [0, 255, 0, 0], [0, 255, 0, 0], [0, 255, 0, 0]].concat();
+        for kind in [TextureKind::Color, TextureKind::Linear] {
+            let levels = mip_chain(2, 2, &data, kind);
+            assert_eq!(levels[0], data);
+            assert_eq!(levels[1], [255, 0, 0, 64]);
+        }
+        // Opaque images filter like before
+        let data = [[255, 0, 0, 255], [0, 0, 255, 255], [0, 0, 255, 255], [255, 0, 0, 255]].concat();
+        assert_eq!(mip_chain(2, 2, &data, TextureKind::Linear)[1], [128, 0, 128, 255]);
+    }
+}
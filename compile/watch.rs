@@ -0,0 +1,85 @@
+use std::{path::{Path, PathBuf}, collections::HashMap, time::{Duration, SystemTime}, fs, process::Command};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Time without further changes before rebuilding, editors often write a file in several steps.
+pub const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+type Snapshot = HashMap<PathBuf, SystemTime>;
+
+/// Polls the asset folders and recompiles whenever something changes, never returns.
+pub fn run(dirs: &crate::Dirs, workers: usize) {
+    println!("Watching {} and {}", dirs.assets.display(), dirs.animations.display());
+    let mut models = snapshot(&dirs.assets);
+    let mut animations = snapshot(&dirs.animations);
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if let Some((current, _)) = changes(&models, &dirs.assets) {
+            crate::compile(dirs, false, workers);
+            models = current;
+        }
+        if let Some((current, changed)) = changes(&animations, &dirs.animations) {
+            // Blender exports the keys of fbx clips first, the compiler compresses them
+            if changed.iter().any(|v| v.extension().is_some_and(|v| v == "fbx")) {
+                blender();
+            }
+            crate::compile(dirs, false, workers);
+            animations = current;
+        }
+    }
+}
+
+/// Waits for the folder to settle and prints what changed,
+/// returns the new snapshot and the changed paths if anything did.
+fn changes(last: &Snapshot, path: &Path) -> Option<(Snapshot, Vec<PathBuf>)> {
+    let mut current = snapshot(path);
+    if &current == last { return None }
+    loop {
+        std::thread::sleep(SETTLE_TIME);
+        let next = snapshot(path);
+        if next == current { break }
+        current = next;
+    }
+    let mut changed = Vec::new();
+    for (path, modified) in &current {
+        match last.get(path) {
+            None => println!("Added: {}", path.display()),
+            Some(v) if v != modified => println!("Changed: {}", path.display()),
+            _ => continue
+        }
+        changed.push(path.clone());
+    }
+    for path in last.keys().filter(|v| !current.contains_key(*v)) {
+        println!("Deleted: {}", path.display());
+        changed.push(path.clone());
+    }
+    Some((current, changed))
+}
+
+fn snapshot(path: impl AsRef<Path>) -> Snapshot {
+    let mut res = HashMap::new();
+    snapshot_dir(path.as_ref(), &mut res);
+    res
+}
+fn snapshot_dir(path: &Path, res: &mut Snapshot) {
+    let entries = match fs::read_dir(path) {
+        Ok(v) => v,
+        Err(_) => return
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            snapshot_dir(&path, res)
+        } else if let Ok(modified) = entry.metadata().and_then(|v| v.modified()) {
+            res.insert(path, modified);
+        }
+    }
+}
+
+/// Keys of fbx animations are exported by `compiler.py` inside Blender and compiled by `animation::fbx`.
+fn blender() {
+    match Command::new("blender").args(["--background", "--python", "compiler.py"]).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => println!("Error compiling animations: blender exited with {}", status),
+        Err(e) => println!("Error compiling animations: could not run blender, {}", e)
+    }
+}
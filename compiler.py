@@ -1,29 +1,44 @@
 import bpy, struct, os, shutil, time
-from io import BufferedWriter
+from io import BufferedWriter, BytesIO
 from pathlib import Path
 
+LOW_MAGIC = b"LOW\0"
+LOW_VERSION = 13
+SECTION_METADATA = 5
+SECTION_CHANNELS = 8
+PROPERTY_TRANSLATION = 0
+PROPERTY_ROTATION = 1
+PROPERTY_SCALE = 2
+INTERPOLATION_LINEAR = 1
+ENCODING_FLOAT = 0
+
 start = time.time()
 
 shutil.rmtree("./.compiled/animation", ignore_errors=True)
 os.makedirs("./.compiled/animations/", exist_ok=True)
 os.makedirs("./assets/animations/", exist_ok=True)
 
+def write_u16(f: BufferedWriter, v: any):
+    f.write(v.to_bytes(2, byteorder='big', signed=False))
 def write_u32(f: BufferedWriter, v: any):
     f.write(v.to_bytes(4, byteorder='big', signed=False))
 def write_u8(f: BufferedWriter, v: any):
     if v > 255: raise Exception("Value is bigger than 255")
     f.write(v.to_bytes(1, byteorder='big', signed=False))
 def write_str(f: BufferedWriter, v: any):
-    f.write(str.encode(str(v))+b'#')
-def write_mat4x4(f: BufferedWriter, mat: any):
-    f.write(struct.pack(">f", mat[0][0])); f.write(struct.pack(">f", mat[1][0]))
-    f.write(struct.pack(">f", mat[2][0])); f.write(struct.pack(">f", mat[3][0]))
-    f.write(struct.pack(">f", mat[0][1])); f.write(struct.pack(">f", mat[1][1]))
-    f.write(struct.pack(">f", mat[2][1])); f.write(struct.pack(">f", mat[3][1]))
-    f.write(struct.pack(">f", mat[0][2])); f.write(struct.pack(">f", mat[1][2]))
-    f.write(struct.pack(">f", mat[2][2])); f.write(struct.pack(">f", mat[3][2]))
-    f.write(struct.pack(">f", mat[0][3])); f.write(struct.pack(">f", mat[1][3]))
-    f.write(struct.pack(">f", mat[2][3])); f.write(struct.pack(">f", mat[3][3]))
+    b = str.encode(str(v))
+    write_u16(f, len(b))
+    f.write(b)
+def write_f32(f: BufferedWriter, v: any):
+    f.write(struct.pack(">f", v))
+def write_channel(f: BufferedWriter, property: int, times: list, values: list):
+    write_u8(f, property)
+    write_u8(f, INTERPOLATION_LINEAR)
+    write_u8(f, ENCODING_FLOAT)
+    write_u32(f, len(times))
+    for t in times: write_f32(f, t)
+    for value in values:
+        for v in value: write_f32(f, v)
 
 def get_armature():
     for object in bpy.data.objects:
@@ -35,45 +50,82 @@ def get_armature():
 def clear_scene():
     bpy.ops.wm.read_factory_settings(use_empty=True)
 
-def initialize_file(path: Path, folder: str, type: bytes):
-    name = path.name.split('.')[0]
+def initialize_file(path: Path, folder: str):
+    """Keys are written next to the clip the compile binary compresses them into."""
     root = Path(f"./.compiled/{folder}/").joinpath(path.parent.relative_to(f"assets/{folder}/"))
     os.makedirs(root, exist_ok=True)
-    f = open(root.joinpath(name+".low"), "wb+")
-    f.write(type)
-    return f
+    return open(root.joinpath(path.with_suffix(".keys.low").name), "wb+")
+
+def write_container(f: BufferedWriter, kind: bytes, sections: list):
+    f.write(LOW_MAGIC)
+    write_u16(f, LOW_VERSION)
+    f.write(kind)
+    write_u8(f, len(sections))
+    offset = 8 + len(sections) * 9
+    for (id, data) in sections:
+        write_u8(f, id)
+        write_u32(f, offset)
+        write_u32(f, len(data))
+        offset += len(data)
+    for (_, data) in sections:
+        f.write(data)
 
-def export_bones(f: BufferedWriter):
-    bones_length = len(bpy.context.selected_pose_bones)
-    if bones_length >= 255: raise Exception("Armature must have less than 255 bones")
-    write_u8(f, bones_length)
+def metadata_section(source: Path):
+    b = BytesIO()
+    write_u16(b, 1)
+    write_str(b, "source")
+    write_str(b, source)
+    return (SECTION_METADATA, b.getvalue())
 
-def set_last_frame():
+def set_frame_range():
     if bpy.data.actions:
         action_list = [action.frame_range for action in bpy.data.actions]
         keys = (sorted(set([item for sublist in action_list for item in sublist])))
+        bpy.context.scene.frame_start = int(keys[0])
         bpy.context.scene.frame_end = int(keys[-1])
     else: raise Exception("No actions found")
 
-def export_frames(f: BufferedWriter):
-    frames = bpy.context.scene.frame_end
-    write_u32(f, frames)
-    for bone in bpy.context.selected_pose_bones:
+def export_channels(f: BufferedWriter):
+    """Local transform of every selected bone at every frame, the compile binary renames and compresses them."""
+    scene = bpy.context.scene
+    frames = range(scene.frame_start, scene.frame_end + 1)
+    fps = scene.render.fps / scene.render.fps_base
+    times = [(frame - scene.frame_start) / fps for frame in frames]
+    bones = bpy.context.selected_pose_bones
+    values = { bone.name: ([], [], []) for bone in bones }
+    for frame in frames:
+        scene.frame_set(frame)
+        bpy.context.view_layer.update()
+        for bone in bones:
+            matrix = bone.parent.matrix.inverted_safe() @ bone.matrix if bone.parent else bone.matrix
+            loc, rot, scale = matrix.decompose()
+            translations, rotations, scales = values[bone.name]
+            # Keep consecutive quaternions in the same hemisphere
+            if rotations and rotations[-1][3] * rot.w + rotations[-1][0] * rot.x + rotations[-1][1] * rot.y + rotations[-1][2] * rot.z < 0: rot.negate()
+            translations.append((loc.x, loc.y, loc.z))
+            rotations.append((rot.x, rot.y, rot.z, rot.w))
+            scales.append((scale.x, scale.y, scale.z))
+    write_f32(f, times[-1] if times else 0)
+    write_u16(f, len(bones))
+    for bone in bones:
+        channels = list(zip((PROPERTY_TRANSLATION, PROPERTY_ROTATION, PROPERTY_SCALE), values[bone.name]))
         write_str(f, bone.name)
-        for frame in range(frames):
-            bpy.context.scene.frame_set(frame)
-            bpy.context.view_layer.update()
-            write_mat4x4(f, bone.matrix @ (bone.parent.matrix.inverted_safe()) if bone.parent else bone.matrix )
+        for _, keys in channels:
+            for v in keys[0]: write_f32(f, v)
+        write_u8(f, len(channels))
+        for property, keys in channels: write_channel(f, property, times, keys)
 
 def export_animation(path: Path):
-    f = initialize_file(path, "animations", b"A")
+    f = initialize_file(path, "animations")
 
     bpy.ops.object.mode_set(mode='POSE')
 
-    export_bones(f)
-    set_last_frame()
-    export_frames(f)
+    if len(bpy.context.selected_pose_bones) > 65535: raise Exception("Armature must have at most 65535 bones")
+    set_frame_range()
+    channels = BytesIO()
+    export_channels(channels)
 
+    write_container(f, b"A", [metadata_section(path), (SECTION_CHANNELS, channels.getvalue())])
     f.close()
 
 for path in Path("assets/animations").glob("**/*.fbx"):
@@ -82,4 +134,4 @@ for path in Path("assets/animations").glob("**/*.fbx"):
     bpy.ops.import_scene.fbx(filepath=str(path))
     export_animation(path)
 
-print(f"Animations compiled in: {(time.time() - start):.1f} sec")
\ No newline at end of file
+print(f"Animation keys exported in: {(time.time() - start):.1f} sec")
\ No newline at end of file
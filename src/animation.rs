@@ -1,35 +1,59 @@
-use std::{path::Path, collections::HashMap};
-use cgmath::Matrix4;
+use std::{path::{Path, PathBuf}, collections::HashMap};
+use crate::{low::{self, Section}, keyframes::{Track, Weights}};
 
 pub struct Animation {
-    pub joints: HashMap<String, Vec<Matrix4<f32>>>,
-    pub frames: usize
+    pub path: PathBuf,
+    pub joints: HashMap<String, Track>,
+    /// Morph target weights by glTF mesh name.
+    pub weights: HashMap<String, Weights>,
+    /// Length of the clip in seconds.
+    pub duration: f32
 }
-#[allow(dead_code)]
 impl Animation {
-    pub fn load(
-        path: impl AsRef<Path>,
-        rename_joints: Option<fn(String)->String>
-    ) -> Self {
-        let mut cursor = crate::cursor::Cursor::new(std::fs::read(path).unwrap());
-        if cursor.read_u8() != b'A' { panic!("Invalid file format") }
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        match Self::read(path.as_ref()) {
+            Ok(v) => v,
+            Err(e) => panic!("Error reading file: {}, {}", path.as_ref().display(), e)
+        }
+    }
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, low::Error> {
+        let file = low::File::read(path.as_ref(), low::Kind::Animation)?;
+        let mut cursor = file.require(Section::Channels)?;
 
-        let joints_length = cursor.read_u8() as usize;
-        let frames_length = cursor.read_u32() as usize;
+        let duration = cursor.read_f32();
+        let joints_length = cursor.read_u16() as usize;
         let mut joints = HashMap::with_capacity(joints_length);
-
         for _ in 0..joints_length {
-            let joint_name = match rename_joints {
-                Some(v) => v(cursor.read_str()),
-                None => cursor.read_str()
-            };
-            let mut joint_frames = Vec::with_capacity(frames_length);
-            for _ in 0..frames_length {
-                joint_frames.push(cursor.read_mat4x4().into());
+            let track = Track::read(&mut cursor)?;
+            joints.insert(track.name.clone(), track);
+        }
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Channels)) }
+        if !duration.is_finite() || duration < 0. { return Err(low::Error::Invalid(format!("Invalid duration: {}", duration))) }
+
+        let mut weights = HashMap::new();
+        if let Some(mut cursor) = file.section(Section::Weights) {
+            for _ in 0..cursor.read_u16() {
+                let track = Weights::read(&mut cursor)?;
+                weights.insert(track.mesh.clone(), track);
             }
-            joints.insert(joint_name, joint_frames);
+            if cursor.overflowed() { return Err(low::Error::Truncated(Section::Weights)) }
         }
 
-        Self { joints, frames: frames_length }
+        Ok(Self { path: path.as_ref().to_path_buf(), joints, weights, duration })
+    }
+    /// Wraps `time` into the clip so it loops.
+    pub fn looped(&self, time: f32) -> f32 {
+        if self.duration > 0. { time % self.duration } else { 0. }
+    }
+    /// Replaces the channels if `path` is this animation, an invalid file is logged and the current frames are kept.
+    pub fn reload(&mut self, path: &Path) {
+        if path != self.path { return }
+        match Self::read(path) {
+            Ok(v) => {
+                *self = v;
+                log::info!("Reloaded animation: {}", path.display());
+            }
+            Err(e) => log::error!("Error reloading animation: {}, {}", path.display(), e)
+        }
     }
 }
\ No newline at end of file
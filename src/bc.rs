@@ -0,0 +1,316 @@
+use crate::low::TextureKind;
+
+/// Interpolation weights of 4 bit BC7 indices.
+const BC7_WEIGHTS: [u32;16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Bytes per 4x4 block, BC7 for color and linear, BC5 for normal and BC4 for mask textures.
+pub const fn block_size(kind: TextureKind) -> usize {
+    match kind {
+        TextureKind::Mask => 8,
+        _ => 16
+    }
+}
+
+/// Encodes a level stored with `kind.channels()` bytes per pixel, edge pixels are repeated
+/// to fill partial blocks.
+#[allow(dead_code, reason = "only the compiler encodes textures")]
+pub fn encode(kind: TextureKind, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+    let channels = kind.channels();
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let mut res = Vec::with_capacity((blocks_x * blocks_y) as usize * block_size(kind));
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let mut block = [[0u8;4];16];
+            for (i, pixel) in block.iter_mut().enumerate() {
+                let x = (bx * 4 + i as u32 % 4).min(width - 1);
+                let y = (by * 4 + i as u32 / 4).min(height - 1);
+                let offset = (y * width + x) as usize * channels;
+                pixel[..channels].copy_from_slice(&data[offset..offset + channels]);
+            }
+            match kind {
+                TextureKind::Color | TextureKind::Linear => res.extend_from_slice(&encode_bc7(&block)),
+                TextureKind::Normal => {
+                    res.extend_from_slice(&encode_bc4(&block.map(|v| v[0])));
+                    res.extend_from_slice(&encode_bc4(&block.map(|v| v[1])));
+                }
+                TextureKind::Mask => res.extend_from_slice(&encode_bc4(&block.map(|v| v[0])))
+            }
+        }
+    }
+    res
+}
+
+/// Decodes a level into `kind.channels()` bytes per pixel. Only reads what `encode` writes: BC7 blocks
+/// in another mode than 6 are an error, the CPU fallback of `texture.rs` relies on this.
+pub fn decode(kind: TextureKind, width: u32, height: u32, data: &[u8]) -> Result<Vec<u8>, String> {
+    let channels = kind.channels();
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let size = block_size(kind);
+    if data.len() != (blocks_x * blocks_y) as usize * size {
+        return Err(format!("Expected {} bytes of blocks, found {}", (blocks_x * blocks_y) as usize * size, data.len()))
+    }
+    let mut res = vec![0; (width * height) as usize * channels];
+    for (i, block) in data.chunks(size).enumerate() {
+        let pixels: [[u8;4];16] = match kind {
+            TextureKind::Color | TextureKind::Linear => decode_bc7(block.try_into().unwrap())?,
+            TextureKind::Normal => {
+                let (r, g) = (decode_bc4(block[..8].try_into().unwrap()), decode_bc4(block[8..].try_into().unwrap()));
+                std::array::from_fn(|i| [r[i], g[i], 0, 255])
+            }
+            TextureKind::Mask => decode_bc4(block.try_into().unwrap()).map(|v| [v, 0, 0, 255])
+        };
+        let (bx, by) = (i as u32 % blocks_x, i as u32 / blocks_x);
+        for (j, pixel) in pixels.iter().enumerate() {
+            let (x, y) = (bx * 4 + j as u32 % 4, by * 4 + j as u32 / 4);
+            if x >= width || y >= height { continue }
+            let offset = (y * width + x) as usize * channels;
+            res[offset..offset + channels].copy_from_slice(&pixel[..channels]);
+        }
+    }
+    Ok(res)
+}
+
+fn bc4_palette(r0: u8, r1: u8) -> [u8;8] {
+    let (a, b) = (r0 as u32, r1 as u32);
+    let mut res = [r0, r1, 0, 0, 0, 0, 0, 255];
+    if r0 > r1 {
+        for i in 1..7 {
+            res[i + 1] = (((7 - i as u32) * a + i as u32 * b + 3) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            res[i + 1] = (((5 - i as u32) * a + i as u32 * b + 2) / 5) as u8;
+        }
+    }
+    res
+}
+
+fn encode_bc4(block: &[u8;16]) -> [u8;8] {
+    let (min, max) = (*block.iter().min().unwrap(), *block.iter().max().unwrap());
+    let palette = bc4_palette(max, min);
+    let mut indices = 0u64;
+    for (i, v) in block.iter().enumerate() {
+        let index = (0..8).min_by_key(|k| (palette[*k] as i32 - *v as i32).abs()).unwrap() as u64;
+        indices |= index << (3 * i);
+    }
+    let mut res = [0;8];
+    res[0] = max;
+    res[1] = min;
+    res[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    res
+}
+
+fn decode_bc4(block: &[u8;8]) -> [u8;16] {
+    let palette = bc4_palette(block[0], block[1]);
+    let mut bytes = [0;8];
+    bytes[..6].copy_from_slice(&block[2..]);
+    let indices = u64::from_le_bytes(bytes);
+    std::array::from_fn(|i| palette[(indices >> (3 * i) & 7) as usize])
+}
+
+/// BC7 mode 6: one subset with 7 bit RGBA endpoints, a p-bit per endpoint and 4 bit indices.
+/// Endpoints start at the extremes along the principal axis and are refined once with least squares.
+fn encode_bc7(block: &[[u8;4];16]) -> [u8;16] {
+    let pixels: Vec<[f32;4]> = block.iter().map(|v| v.map(|c| c as f32)).collect();
+    let mut mean = [0f32;4];
+    for p in &pixels {
+        for c in 0..4 { mean[c] += p[c] / 16. }
+    }
+    let mut covariance = [[0f32;4];4];
+    for p in &pixels {
+        for i in 0..4 {
+            for j in 0..4 {
+                covariance[i][j] += (p[i] - mean[i]) * (p[j] - mean[j]);
+            }
+        }
+    }
+    let mut axis = [1f32;4];
+    for _ in 0..8 {
+        let mut next = [0f32;4];
+        for i in 0..4 {
+            for j in 0..4 { next[i] += covariance[i][j] * axis[j] }
+        }
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < 1e-6 { break }
+        axis = next.map(|v| v / length);
+    }
+    let project = |p: &[f32;4]| (0..4).map(|c| (p[c] - mean[c]) * axis[c]).sum::<f32>();
+    let (min, max) = pixels.iter().map(project).fold((f32::MAX, f32::MIN), |(a, b), t| (a.min(t), b.max(t)));
+    let e0: [f32;4] = std::array::from_fn(|c| mean[c] + axis[c] * min);
+    let e1: [f32;4] = std::array::from_fn(|c| mean[c] + axis[c] * max);
+
+    let mut best = quantize_bc7(block, e0, e1);
+    // Least squares endpoints for the chosen indices
+    let (mut aa, mut ab, mut bb) = (0f32, 0f32, 0f32);
+    let (mut ax, mut bx) = ([0f32;4], [0f32;4]);
+    for (i, p) in pixels.iter().enumerate() {
+        let w = BC7_WEIGHTS[best.2[i] as usize] as f32 / 64.;
+        let (a, b) = (1. - w, w);
+        aa += a * a; ab += a * b; bb += b * b;
+        for c in 0..4 {
+            ax[c] += a * p[c];
+            bx[c] += b * p[c];
+        }
+    }
+    let det = aa * bb - ab * ab;
+    if det.abs() > 1e-6 {
+        let e0 = std::array::from_fn(|c| (bb * ax[c] - ab * bx[c]) / det);
+        let e1 = std::array::from_fn(|c| (aa * bx[c] - ab * ax[c]) / det);
+        let refined = quantize_bc7(block, e0, e1);
+        if refined.3 < best.3 { best = refined }
+    }
+    let (mut endpoints, mut pbits, mut indices, _) = best;
+    if indices[0] >= 8 {
+        endpoints.swap(0, 1);
+        pbits.swap(0, 1);
+        indices = indices.map(|v| 15 - v);
+    }
+
+    let mut bits = 1u128 << 6;
+    let mut offset = 7;
+    let mut push = |v: u128, length: u32| {
+        bits |= v << offset;
+        offset += length;
+    };
+    for (c0, c1) in endpoints[0].iter().zip(&endpoints[1]) {
+        push(*c0 as u128, 7);
+        push(*c1 as u128, 7);
+    }
+    push(pbits[0] as u128, 1);
+    push(pbits[1] as u128, 1);
+    for (i, index) in indices.iter().enumerate() {
+        push(*index as u128, if i == 0 { 3 } else { 4 });
+    }
+    bits.to_le_bytes()
+}
+
+/// Tries every p-bit pair and returns the 7 bit endpoints, p-bits, indices and squared error of the best one.
+fn quantize_bc7(block: &[[u8;4];16], e0: [f32;4], e1: [f32;4]) -> ([[u8;4];2], [u8;2], [u8;16], u32) {
+    let mut best = ([[0;4];2], [0;2], [0;16], u32::MAX);
+    for p0 in 0..2u8 {
+        for p1 in 0..2u8 {
+            let quantize = |e: [f32;4], p: u8| e.map(|v| ((v.clamp(0., 255.) - p as f32) / 2.).round().clamp(0., 127.) as u8);
+            let endpoints = [quantize(e0, p0), quantize(e1, p1)];
+            let palette = bc7_palette(endpoints, [p0, p1]);
+            let mut indices = [0;16];
+            let mut error = 0;
+            for (i, pixel) in block.iter().enumerate() {
+                let (index, e) = palette.iter().enumerate()
+                    .map(|(k, v)| (k, (0..4).map(|c| (v[c] as i32 - pixel[c] as i32).pow(2) as u32).sum::<u32>()))
+                    .min_by_key(|v| v.1).unwrap();
+                indices[i] = index as u8;
+                error += e;
+            }
+            if error < best.3 { best = (endpoints, [p0, p1], indices, error) }
+        }
+    }
+    best
+}
+
+fn bc7_palette(endpoints: [[u8;4];2], pbits: [u8;2]) -> [[u8;4];16] {
+    let e0 = endpoints[0].map(|v| (v << 1 | pbits[0]) as u32);
+    let e1 = endpoints[1].map(|v| (v << 1 | pbits[1]) as u32);
+    BC7_WEIGHTS.map(|w| std::array::from_fn(|c| (((64 - w) * e0[c] + w * e1[c] + 32) >> 6) as u8))
+}
+
+fn decode_bc7(block: &[u8;16]) -> Result<[[u8;4];16], String> {
+    let bits = u128::from_le_bytes(*block);
+    let mode = bits.trailing_zeros();
+    if mode != 6 { return Err(format!("Unsupported BC7 mode: {}", mode)) }
+    let mut offset = 7;
+    let mut read = |length: u32| {
+        let v = (bits >> offset) & ((1 << length) - 1);
+        offset += length;
+        v as u8
+    };
+    // Stored as R0 R1 G0 G1 B0 B1 A0 A1
+    let components: [u8;8] = std::array::from_fn(|_| read(7));
+    let endpoints = [std::array::from_fn(|c| components[c * 2]), std::array::from_fn(|c| components[c * 2 + 1])];
+    let pbits = [read(1), read(1)];
+    let palette = bc7_palette(endpoints, pbits);
+    Ok(std::array::from_fn(|i| palette[read(if i == 0 { 3 } else { 4 }) as usize]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smooth gradients with a little noise, like the texels of a real texture.
+    fn image(width: u32, height: u32, channels: usize) -> Vec<u8> {
+        let mut state = 0x2545f491u32;
+        let mut res = Vec::with_capacity((width * height) as usize * channels);
+        for y in 0..height {
+            for x in 0..width {
+                for c in 0..channels as u32 {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    let v = x * (3 + c) + y * (6 - c);
+                    res.push((v + state % 9).min(255) as u8);
+                }
+            }
+        }
+        res
+    }
+
+    /// Largest and mean absolute difference per channel.
+    fn round_trip(kind: TextureKind, width: u32, height: u32, data: &[u8]) -> (u8, f64) {
+        let encoded = encode(kind, width, height, data);
+        assert_eq!(encoded.len(), (width.div_ceil(4) * height.div_ceil(4)) as usize * block_size(kind));
+        let decoded = decode(kind, width, height, &encoded).unwrap();
+        assert_eq!(decoded.len(), data.len());
+        let errors = data.iter().zip(&decoded).map(|(a, b)| a.abs_diff(*b));
+        (errors.clone().max().unwrap(), errors.map(|v| v as f64).sum::<f64>() / data.len() as f64)
+    }
+
+    #[test]
+    fn bc7_round_trips_within_bounds() {
+        for kind in [TextureKind::Color, TextureKind::Linear] {
+            let (max, mean) = round_trip(kind, 32, 16, &image(32, 16, 4));
+            assert!(max <= 12 && mean <= 3., "{:?}: max {} mean {}", kind, max, mean);
+        }
+    }
+
+    #[test]
+    fn bc5_round_trips_within_bounds() {
+        let (max, mean) = round_trip(TextureKind::Normal, 32, 16, &image(32, 16, 2));
+        assert!(max <= 3 && mean <= 1.5, "max {} mean {}", max, mean);
+    }
+
+    #[test]
+    fn bc4_round_trips_within_bounds() {
+        let (max, mean) = round_trip(TextureKind::Mask, 32, 16, &image(32, 16, 1));
+        assert!(max <= 3 && mean <= 1.5, "max {} mean {}", max, mean);
+    }
+
+    #[test]
+    fn partial_blocks_round_trip() {
+        for (kind, bound) in [(TextureKind::Color, 12), (TextureKind::Normal, 3), (TextureKind::Mask, 3)] {
+            for (width, height) in [(1, 1), (3, 5), (13, 6)] {
+                let (max, _) = round_trip(kind, width, height, &image(width, height, kind.channels()));
+                assert!(max <= bound, "{:?} {}x{}: max {}", kind, width, height, max);
+            }
+        }
+    }
+
+    /// BC4 stores 8 bit endpoints, BC7 mode 6 shares the p-bit between the channels of an endpoint
+    /// so channels of different parity can be off by one.
+    #[test]
+    fn solid_blocks_round_trip() {
+        for color in [[0, 0, 0, 0], [255, 255, 255, 255], [200, 17, 96, 128], [1, 254, 127, 3]] {
+            for kind in [TextureKind::Color, TextureKind::Normal, TextureKind::Mask] {
+                let data: Vec<u8> = (0..16).flat_map(|_| color[..kind.channels()].to_vec()).collect();
+                let bound = if kind == TextureKind::Color { 1 } else { 0 };
+                assert!(round_trip(kind, 4, 4, &data).0 <= bound, "{:?} {:?}", kind, color);
+            }
+        }
+    }
+
+    #[test]
+    fn other_bc7_modes_are_rejected() {
+        let mut block = [0u8;16];
+        block[0] = 1 << 5;
+        assert!(decode(TextureKind::Color, 4, 4, &block).is_err());
+        assert!(decode(TextureKind::Color, 4, 4, &[0;16]).is_err());
+    }
+}
@@ -3,7 +3,7 @@ use futures::executor::block_on;
 pub fn new(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
     block_on(adapter.request_device(
         &wgpu::DeviceDescriptor {
-            features: wgpu::Features::empty(),
+            features: adapter.features() & wgpu::Features::TEXTURE_COMPRESSION_BC,
             limits: wgpu::Limits::default(),
             label: None
         },
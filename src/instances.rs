@@ -4,16 +4,19 @@ use wgpu::util::DeviceExt;
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceTransform {
     pub position: [f32;3],
-    pub scale: [f32;3]
+    pub scale: [f32;3],
+    /// xyzw quaternion applied after the scale.
+    pub rotation: [f32;4]
 }
 
 pub struct Instances {
     pub buffer: wgpu::Buffer,
     pub buffer_len: u32,
+    /// Instance range drawn with each level of detail.
+    pub lods: Vec<std::ops::Range<u32>>,
     transforms: Vec<InstanceTransform>,
     needs_update: bool
 }
-#[allow(dead_code)]
 impl Instances {
     pub fn new(device: &wgpu::Device, transforms: Vec<InstanceTransform>) -> Self {
         Self {
@@ -25,18 +28,38 @@ impl Instances {
                 }
             ),
             buffer_len: transforms.len() as u32,
+            lods: std::iter::once(0..transforms.len() as u32).collect(),
             transforms,
             needs_update: false
         }
     }
+    #[allow(dead_code, reason = "instancing API, the demo spawns its instances once")]
     pub fn clear(&mut self) {
         self.transforms.clear();
         self.needs_update = true;
     }
+    #[allow(dead_code, reason = "instancing API, the demo spawns its instances once")]
     pub fn add(&mut self, transform: InstanceTransform) {
         self.transforms.push(transform);
         self.needs_update = true;
     }
+    /// Orders the instances by their level of detail, from 0 to `levels` - 1.
+    pub fn sort_lods(&mut self, levels: usize, lod: impl Fn(&InstanceTransform) -> usize) {
+        let keys: Vec<usize> = self.transforms.iter().map(|v| lod(v).min(levels - 1)).collect();
+        if !keys.windows(2).all(|v| v[0] <= v[1]) {
+            let mut order: Vec<usize> = (0..keys.len()).collect();
+            order.sort_by_key(|v| keys[*v]);
+            self.transforms = order.iter().map(|v| self.transforms[*v]).collect();
+            self.needs_update = true;
+        }
+        let mut start = 0;
+        self.lods = (0..levels).map(|level| {
+            let end = start + keys.iter().filter(|v| **v == level).count() as u32;
+            let range = start..end;
+            start = end;
+            range
+        }).collect();
+    }
     pub fn update(&mut self, device: &wgpu::Device) {
         if self.needs_update {
             self.buffer = device.create_buffer_init(
@@ -47,6 +70,9 @@ impl Instances {
                 }
             );
             self.buffer_len = self.transforms.len() as u32;
+            if self.lods.last().map_or(0, |v| v.end) != self.buffer_len {
+                self.lods = std::iter::once(0..self.buffer_len).collect();
+            }
             self.needs_update = false;
         }
     }
@@ -0,0 +1,530 @@
+use cgmath::{Matrix3, Matrix4, Quaternion, Vector3, Vector4, InnerSpace, SquareMatrix, VectorSpace};
+use crate::{cursor::Cursor, low::{self, Section}};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Property { Translation, Rotation, Scale }
+impl Property {
+    #[allow(dead_code, reason = "only the compiler writes animations")]
+    pub const fn id(&self) -> u8 {
+        match self {
+            Property::Translation => 0,
+            Property::Rotation => 1,
+            Property::Scale => 2
+        }
+    }
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Property::Translation),
+            1 => Some(Property::Rotation),
+            2 => Some(Property::Scale),
+            _ => None
+        }
+    }
+    /// Translation and scale are xyz, rotation is a xyzw quaternion.
+    pub const fn components(&self) -> usize {
+        match self {
+            Property::Rotation => 4,
+            _ => 3
+        }
+    }
+}
+
+/// Same modes as glTF animation samplers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation { Step, Linear, CubicSpline }
+impl Interpolation {
+    #[allow(dead_code, reason = "only the compiler writes animations")]
+    pub const fn id(&self) -> u8 {
+        match self {
+            Interpolation::Step => 0,
+            Interpolation::Linear => 1,
+            Interpolation::CubicSpline => 2
+        }
+    }
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Interpolation::Step),
+            1 => Some(Interpolation::Linear),
+            2 => Some(Interpolation::CubicSpline),
+            _ => None
+        }
+    }
+}
+
+/// How channel values are stored in the file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Float,
+    /// Unit quaternions as the 3 smallest components in 15 bits each plus the index of the largest one, 6 bytes per key.
+    SmallestThree
+}
+impl Encoding {
+    #[allow(dead_code, reason = "only the compiler writes animations")]
+    pub const fn id(&self) -> u8 {
+        match self {
+            Encoding::Float => 0,
+            Encoding::SmallestThree => 1
+        }
+    }
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Encoding::Float),
+            1 => Some(Encoding::SmallestThree),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Channel {
+    pub property: Property,
+    pub interpolation: Interpolation,
+    pub encoding: Encoding,
+    pub times: Vec<f32>,
+    /// One value per key, cubic spline keys are stored as (in tangent, value, out tangent).
+    pub values: Vec<[f32;4]>
+}
+impl Channel {
+    pub fn value(&self, key: usize) -> [f32;4] {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.values[key * 3 + 1],
+            _ => self.values[key]
+        }
+    }
+    /// Evaluates the channel at `time`, times outside of the keys are clamped.
+    pub fn sample(&self, time: f32) -> [f32;4] {
+        let times = &self.times;
+        if times.len() == 1 || time <= times[0] { return self.value(0) }
+        let last = times.len() - 1;
+        if time >= times[last] { return self.value(last) }
+        let k = times.partition_point(|v| *v <= time) - 1;
+        let dt = times[k + 1] - times[k];
+        let s = (time - times[k]) / dt;
+        let res = match self.interpolation {
+            Interpolation::Step => self.value(k),
+            Interpolation::Linear => match self.property {
+                Property::Rotation => quaternion_to_array(array_to_quaternion(self.value(k)).slerp(array_to_quaternion(self.value(k + 1)), s)),
+                _ => {
+                    let v = Vector4::from(self.value(k)).lerp(Vector4::from(self.value(k + 1)), s);
+                    v.into()
+                }
+            },
+            Interpolation::CubicSpline => {
+                let (s2, s3) = (s * s, s * s * s);
+                let out_tangent = self.values[k * 3 + 2];
+                let in_tangent = self.values[(k + 1) * 3];
+                let (v0, v1) = (self.value(k), self.value(k + 1));
+                let mut res = [0.;4];
+                for i in 0..4 {
+                    res[i] = (2. * s3 - 3. * s2 + 1.) * v0[i] + (s3 - 2. * s2 + s) * dt * out_tangent[i]
+                        + (-2. * s3 + 3. * s2) * v1[i] + (s3 - s2) * dt * in_tangent[i];
+                }
+                res
+            }
+        };
+        match self.property {
+            Property::Rotation => quaternion_to_array(array_to_quaternion(res).normalize()),
+            _ => res
+        }
+    }
+    #[allow(dead_code, reason = "used by the compiler")]
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.)
+    }
+    pub fn read(cursor: &mut Cursor) -> Result<Self, low::Error> {
+        let property = Property::from_id(cursor.read_u8());
+        let interpolation = Interpolation::from_id(cursor.read_u8());
+        let encoding = Encoding::from_id(cursor.read_u8());
+        let (property, interpolation, encoding) = match (property, interpolation, encoding) {
+            (Some(p), Some(i), Some(e)) => (p, i, e),
+            _ => return Err(low::Error::Invalid("Invalid channel property, interpolation or encoding".into()))
+        };
+        if encoding == Encoding::SmallestThree && (property != Property::Rotation || interpolation == Interpolation::CubicSpline) {
+            return Err(low::Error::Invalid("Only step and linear rotations can be quantized".into()))
+        }
+        let keys = cursor.read_u32() as usize;
+        let values_length = if interpolation == Interpolation::CubicSpline { keys * 3 } else { keys };
+        if keys == 0 { return Err(low::Error::Invalid("Channel has no keys".into())) }
+        let value_size = match encoding {
+            Encoding::Float => property.components() * 4,
+            Encoding::SmallestThree => 6
+        };
+        if keys * 4 + values_length * value_size > cursor.remaining() { return Err(low::Error::Truncated(Section::Channels)) }
+        let times: Vec<f32> = (0..keys).map(|_| cursor.read_f32()).collect();
+        if times.windows(2).any(|v| v[1] < v[0]) {
+            return Err(low::Error::Invalid("Channel keys are not sorted".into()))
+        }
+        let values = (0..values_length).map(|_| match encoding {
+            Encoding::Float => {
+                let mut v = [0.;4];
+                for c in v.iter_mut().take(property.components()) {
+                    *c = cursor.read_f32();
+                }
+                v
+            }
+            Encoding::SmallestThree => {
+                let b = cursor.read_bytes(6);
+                decode_smallest_three(b.iter().fold(0u64, |a, b| a << 8 | *b as u64))
+            }
+        }).collect();
+        Ok(Self { property, interpolation, encoding, times, values })
+    }
+    #[allow(dead_code, reason = "only the compiler writes animations")]
+    pub fn write(&self, b: &mut Vec<u8>) {
+        b.push(self.property.id());
+        b.push(self.interpolation.id());
+        b.push(self.encoding.id());
+        b.extend_from_slice(&(self.times.len() as u32).to_be_bytes());
+        for v in &self.times {
+            b.extend_from_slice(&v.to_be_bytes());
+        }
+        for v in &self.values {
+            match self.encoding {
+                Encoding::Float => for c in v.iter().take(self.property.components()) {
+                    b.extend_from_slice(&c.to_be_bytes());
+                },
+                Encoding::SmallestThree => b.extend_from_slice(&encode_smallest_three(*v).to_be_bytes()[2..])
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trs {
+    pub translation: [f32;3],
+    /// xyzw quaternion
+    pub rotation: [f32;4],
+    pub scale: [f32;3]
+}
+impl Trs {
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(Vector3::from(self.translation))
+            * Matrix4::from(array_to_quaternion(self.rotation))
+            * Matrix4::from_nonuniform_scale(self.scale[0], self.scale[1], self.scale[2])
+    }
+    /// Decomposes an affine matrix without shear.
+    pub fn from_matrix(m: Matrix4<f32>) -> Self {
+        let mut scale = [m.x.truncate().magnitude(), m.y.truncate().magnitude(), m.z.truncate().magnitude()];
+        if m.determinant() < 0. { scale[0] = -scale[0] }
+        let axis = |v: Vector4<f32>, s: f32| if s != 0. { v.truncate() / s } else { v.truncate() };
+        let rotation = Quaternion::from(Matrix3::from_cols(axis(m.x, scale[0]), axis(m.y, scale[1]), axis(m.z, scale[2])));
+        Self {
+            translation: m.w.truncate().into(),
+            rotation: quaternion_to_array(rotation.normalize()),
+            scale
+        }
+    }
+    #[allow(dead_code, reason = "used by the compiler")]
+    pub fn get(&self, property: Property) -> [f32;4] {
+        match property {
+            Property::Translation => [self.translation[0], self.translation[1], self.translation[2], 0.],
+            Property::Rotation => self.rotation,
+            Property::Scale => [self.scale[0], self.scale[1], self.scale[2], 0.]
+        }
+    }
+    pub fn set(&mut self, property: Property, v: [f32;4]) {
+        match property {
+            Property::Translation => self.translation = [v[0], v[1], v[2]],
+            Property::Rotation => self.rotation = v,
+            Property::Scale => self.scale = [v[0], v[1], v[2]]
+        }
+    }
+}
+
+/// Channels animating one joint relative to its parent, properties without a channel keep the rest value.
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub name: String,
+    pub rest: Trs,
+    pub channels: Vec<Channel>
+}
+impl Track {
+    #[allow(dead_code, reason = "only the game plays animations")]
+    pub fn sample(&self, time: f32) -> Matrix4<f32> {
+        self.sample_trs(time).matrix()
+    }
+    pub fn sample_trs(&self, time: f32) -> Trs {
+        let mut trs = self.rest;
+        for channel in &self.channels {
+            trs.set(channel.property, channel.sample(time));
+        }
+        trs
+    }
+    pub fn read(cursor: &mut Cursor) -> Result<Self, low::Error> {
+        let name = cursor.read_str();
+        let rest = Trs { translation: cursor.read_vec3(), rotation: cursor.read_vec4(), scale: cursor.read_vec3() };
+        let channels_length = cursor.read_u8() as usize;
+        let mut channels = Vec::with_capacity(channels_length);
+        for _ in 0..channels_length {
+            channels.push(Channel::read(cursor)?);
+        }
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Channels)) }
+        Ok(Self { name, rest, channels })
+    }
+    #[allow(dead_code, reason = "only the compiler writes animations")]
+    pub fn write(&self, b: &mut Vec<u8>) {
+        low::append_str(b, &self.name);
+        for c in self.rest.translation.iter().chain(&self.rest.rotation).chain(&self.rest.scale) {
+            b.extend_from_slice(&c.to_be_bytes());
+        }
+        b.push(self.channels.len() as u8);
+        for channel in &self.channels {
+            channel.write(b);
+        }
+    }
+}
+
+/// Morph target weights of one glTF mesh, every key holds one weight per target.
+#[derive(Clone, Debug)]
+pub struct Weights {
+    pub mesh: String,
+    pub interpolation: Interpolation,
+    pub targets: usize,
+    pub times: Vec<f32>,
+    /// `targets` values per key, cubic spline keys are stored as (in tangents, values, out tangents).
+    pub values: Vec<f32>
+}
+impl Weights {
+    fn key(&self, key: usize, part: usize) -> &[f32] {
+        let i = match self.interpolation {
+            Interpolation::CubicSpline => key * 3 + part,
+            _ => key
+        };
+        &self.values[i * self.targets..(i + 1) * self.targets]
+    }
+    /// Evaluates the weights at `time` into `res`, times outside of the keys are clamped.
+    #[allow(dead_code, reason = "only the game plays animations")]
+    pub fn sample(&self, time: f32, res: &mut [f32]) {
+        let times = &self.times;
+        let last = times.len() - 1;
+        let (k, s) = match times.partition_point(|v| *v <= time) {
+            0 => (0, 0.),
+            v if v > last => (last, 0.),
+            v => (v - 1, (time - times[v - 1]) / (times[v] - times[v - 1]))
+        };
+        let v0 = self.key(k, 1);
+        if s == 0. || self.interpolation == Interpolation::Step {
+            res.iter_mut().zip(v0).for_each(|(r, v)| *r = *v);
+            return
+        }
+        let v1 = self.key(k + 1, 1);
+        match self.interpolation {
+            Interpolation::CubicSpline => {
+                let dt = times[k + 1] - times[k];
+                let (s2, s3) = (s * s, s * s * s);
+                let (out_tangent, in_tangent) = (self.key(k, 2), self.key(k + 1, 0));
+                for (i, r) in res.iter_mut().enumerate().take(self.targets) {
+                    *r = (2. * s3 - 3. * s2 + 1.) * v0[i] + (s3 - 2. * s2 + s) * dt * out_tangent[i]
+                        + (-2. * s3 + 3. * s2) * v1[i] + (s3 - s2) * dt * in_tangent[i];
+                }
+            }
+            _ => res.iter_mut().zip(v0.iter().zip(v1)).for_each(|(r, (a, b))| *r = a + (b - a) * s)
+        }
+    }
+    #[allow(dead_code, reason = "used by the compiler")]
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.)
+    }
+    pub fn read(cursor: &mut Cursor) -> Result<Self, low::Error> {
+        let mesh = cursor.read_str();
+        let interpolation = Interpolation::from_id(cursor.read_u8())
+            .ok_or_else(|| low::Error::Invalid(format!("Invalid weights interpolation of mesh {}", mesh)))?;
+        let targets = cursor.read_u16() as usize;
+        let keys = cursor.read_u32() as usize;
+        let values_length = keys * targets * if interpolation == Interpolation::CubicSpline { 3 } else { 1 };
+        if keys == 0 || targets == 0 { return Err(low::Error::Invalid(format!("Weights of mesh {} have no keys or targets", mesh))) }
+        if (keys + values_length) * 4 > cursor.remaining() { return Err(low::Error::Truncated(Section::Weights)) }
+        let times: Vec<f32> = (0..keys).map(|_| cursor.read_f32()).collect();
+        if times.windows(2).any(|v| v[1] < v[0]) {
+            return Err(low::Error::Invalid("Weights keys are not sorted".into()))
+        }
+        let values = (0..values_length).map(|_| cursor.read_f32()).collect();
+        Ok(Self { mesh, interpolation, targets, times, values })
+    }
+    #[allow(dead_code, reason = "only the compiler writes animations")]
+    pub fn write(&self, b: &mut Vec<u8>) {
+        low::append_str(b, &self.mesh);
+        b.push(self.interpolation.id());
+        b.extend_from_slice(&(self.targets as u16).to_be_bytes());
+        b.extend_from_slice(&(self.times.len() as u32).to_be_bytes());
+        for v in self.times.iter().chain(&self.values) {
+            b.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+}
+
+#[inline]
+pub fn array_to_quaternion(v: [f32;4]) -> Quaternion<f32> {
+    Quaternion::new(v[3], v[0], v[1], v[2])
+}
+#[inline]
+pub fn quaternion_to_array(v: Quaternion<f32>) -> [f32;4] {
+    [v.v.x, v.v.y, v.v.z, v.s]
+}
+
+const SMALLEST_THREE_RANGE: f32 = std::f32::consts::FRAC_1_SQRT_2;
+const SMALLEST_THREE_MAX: f32 = 32767.;
+
+/// Packs a unit xyzw quaternion into the low 48 bits.
+#[allow(dead_code, reason = "only the compiler writes animations")]
+pub fn encode_smallest_three(q: [f32;4]) -> u64 {
+    let largest = (0..4).fold(0, |a, i| if q[i].abs() > q[a].abs() { i } else { a });
+    let sign = if q[largest] < 0. { -1. } else { 1. };
+    let mut res = largest as u64;
+    for (i, v) in q.iter().enumerate() {
+        if i == largest { continue }
+        let v = ((v * sign / SMALLEST_THREE_RANGE).clamp(-1., 1.) * 0.5 + 0.5) * SMALLEST_THREE_MAX;
+        res = res << 15 | v.round() as u64;
+    }
+    res
+}
+pub fn decode_smallest_three(v: u64) -> [f32;4] {
+    let largest = (v >> 45 & 3) as usize;
+    let mut res = [0.;4];
+    let mut sum = 0.;
+    let mut shift = 30;
+    for (i, c) in res.iter_mut().enumerate() {
+        if i == largest { continue }
+        *c = ((v >> shift & 0x7fff) as f32 / SMALLEST_THREE_MAX * 2. - 1.) * SMALLEST_THREE_RANGE;
+        sum += *c * *c;
+        shift -= 15;
+    }
+    res[largest] = (1. - sum).max(0.).sqrt();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Angle between two unit quaternions in radians, with atan2 as acos is not precise enough near 1.
+    fn angle(a: [f32;4], b: [f32;4]) -> f64 {
+        let sign = if a.iter().zip(&b).map(|(a, b)| a * b).sum::<f32>() < 0. { -1. } else { 1. };
+        let length = |f: &dyn Fn(f64, f64) -> f64| a.iter().zip(&b).map(|(a, b)| f(*a as f64, sign * *b as f64).powi(2)).sum::<f64>().sqrt();
+        4. * length(&|a, b| a - b).atan2(length(&|a, b| a + b))
+    }
+    fn normalize(q: [f32;4]) -> [f32;4] {
+        let length = q.iter().map(|v| v * v).sum::<f32>().sqrt();
+        q.map(|v| v / length)
+    }
+
+    fn channel(property: Property, interpolation: Interpolation, times: Vec<f32>, values: Vec<[f32;4]>) -> Channel {
+        Channel { property, interpolation, encoding: Encoding::Float, times, values }
+    }
+    fn assert_close(a: [f32;4], b: [f32;4]) {
+        assert!(a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn step_holds_each_key_until_the_next() {
+        let (a, b, c) = ([1., 0., 0., 0.], [2., 0., 0., 0.], [3., 0., 0., 0.]);
+        let channel = channel(Property::Translation, Interpolation::Step, vec![0., 1., 2.], vec![a, b, c]);
+        for (time, value) in [(-1., a), (0., a), (0.5, a), (0.999, a), (1., b), (1.5, b), (2., c), (10., c)] {
+            assert_eq!(channel.sample(time), value, "at {}", time);
+        }
+    }
+
+    #[test]
+    fn linear_interpolates_between_keys_and_clamps_outside() {
+        let channel = channel(Property::Scale, Interpolation::Linear, vec![1., 2., 4.], vec![[0., 0., 0., 0.], [1., 2., 3., 0.], [3., 2., 1., 0.]]);
+        assert_eq!(channel.sample(0.), [0.;4]);
+        assert_eq!(channel.sample(1.), [0.;4]);
+        assert_close(channel.sample(1.5), [0.5, 1., 1.5, 0.]);
+        assert_eq!(channel.sample(2.), [1., 2., 3., 0.]);
+        assert_close(channel.sample(3.), [2., 2., 2., 0.]);
+        assert_eq!(channel.sample(4.), [3., 2., 1., 0.]);
+        assert_eq!(channel.sample(8.), [3., 2., 1., 0.]);
+    }
+
+    #[test]
+    fn cubic_spline_uses_the_key_tangents() {
+        // Tangents matching the slope between the keys reproduce the line
+        let line = channel(Property::Translation, Interpolation::CubicSpline, vec![0., 2.],
+            vec![[0.5, 0., 0., 0.], [0., 0., 0., 0.], [0.5, 0., 0., 0.], [0.5, 0., 0., 0.], [1., 0., 0., 0.], [0.5, 0., 0., 0.]]);
+        for time in [0.25, 0.5, 1., 1.5] {
+            assert_close(line.sample(time), [time / 2., 0., 0., 0.]);
+        }
+        // Flat tangents ease in and out, keys return their value and not a tangent
+        let ease = channel(Property::Translation, Interpolation::CubicSpline, vec![0., 1.],
+            vec![[9., 9., 9., 0.], [0., 0., 0., 0.], [0.;4], [0.;4], [1., 1., 1., 0.], [9., 9., 9., 0.]]);
+        assert_eq!(ease.sample(-1.), [0.;4]);
+        assert_eq!(ease.sample(0.), [0.;4]);
+        assert_close(ease.sample(0.25), [0.15625, 0.15625, 0.15625, 0.]);
+        assert_close(ease.sample(0.5), [0.5, 0.5, 0.5, 0.]);
+        assert_eq!(ease.sample(1.), [1., 1., 1., 0.]);
+        assert_eq!(ease.sample(2.), [1., 1., 1., 0.]);
+    }
+
+    #[test]
+    fn single_key_channels_are_constant() {
+        let value = [0.5, -1., 2., 0.];
+        for interpolation in [Interpolation::Step, Interpolation::Linear] {
+            let channel = channel(Property::Translation, interpolation, vec![0.5], vec![value]);
+            for time in [-1., 0.5, 3.] {
+                assert_eq!(channel.sample(time), value);
+            }
+        }
+        let channel = channel(Property::Translation, Interpolation::CubicSpline, vec![0.5], vec![[9.;4], value, [9.;4]]);
+        assert_eq!(channel.sample(1.), value);
+    }
+
+    #[test]
+    fn rotations_take_the_shortest_path() {
+        let (sin, cos) = (std::f32::consts::FRAC_PI_4.sin(), std::f32::consts::FRAC_PI_4.cos());
+        // The same 90 degree rotation about y with both signs, halfway is 45 degrees either way
+        for end in [[0., sin, 0., cos], [0., -sin, 0., -cos]] {
+            let channel = channel(Property::Rotation, Interpolation::Linear, vec![0., 1.], vec![[0., 0., 0., 1.], end]);
+            let half = channel.sample(0.5);
+            assert!((angle([0., 0., 0., 1.], half) - std::f64::consts::FRAC_PI_4).abs() < 1e-5, "{:?}", half);
+            assert!(angle(half, end) < std::f64::consts::FRAC_PI_4 + 1e-5);
+            assert!((half.iter().map(|v| v * v).sum::<f32>() - 1.).abs() < 1e-5);
+        }
+        // Step rotations return the keys as they are
+        let channel = channel(Property::Rotation, Interpolation::Step, vec![0., 1.], vec![[0., 0., 0., 1.], [0., -sin, 0., -cos]]);
+        assert_close(channel.sample(1.), [0., -sin, 0., -cos]);
+    }
+
+    // Each of the 3 stored components is off by half a step at most, the largest one follows from them
+    const STEP: f32 = 2. * SMALLEST_THREE_RANGE / SMALLEST_THREE_MAX;
+    const MAX_ANGLE: f64 = 2e-4;
+
+    #[test]
+    fn smallest_three_round_trips_random_quaternions() {
+        let mut seed = 0x2545f4914f6cdd1du64;
+        let mut random = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed >> 40) as f32 / (1u64 << 24) as f32 * 2. - 1.
+        };
+        for _ in 0..100000 {
+            let q = normalize([random(), random(), random(), random()]);
+            let decoded = decode_smallest_three(encode_smallest_three(q));
+            assert!(angle(q, decoded) < MAX_ANGLE, "{:?} decoded as {:?}", q, decoded);
+            assert!((decoded.iter().map(|v| v * v).sum::<f32>() - 1.).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn smallest_three_keeps_the_largest_component_at_each_index() {
+        for largest in 0..4 {
+            for sign in [1., -1.] {
+                let mut q = [0.3, -0.2, 0.1, 0.25];
+                q[largest] = 0.9 * sign;
+                let q = normalize(q);
+                let decoded = decode_smallest_three(encode_smallest_three(q));
+                assert_eq!(encode_smallest_three(q) >> 45, largest as u64);
+                // The decoded largest component is positive, the same rotation as q or -q
+                let q = if sign < 0. { q.map(|v| -v) } else { q };
+                for (a, b) in q.iter().zip(decoded) {
+                    assert!((a - b).abs() <= STEP, "{:?} decoded as {:?}", q, decoded);
+                }
+                assert!(angle(q, decoded) < MAX_ANGLE);
+            }
+        }
+        // Negative w identity and components at the edge of the range
+        for q in [[0., 0., 0., -1.], normalize([1., 1., 0., 0.]), normalize([-1., 0., 0., 1.])] {
+            assert!(angle(q, decode_smallest_three(encode_smallest_three(q))) < MAX_ANGLE);
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use std::{path::Path, fmt};
+use crate::cursor::Cursor;
+
+pub const MAGIC: [u8;4] = *b"LOW\0";
+pub const VERSION: u16 = 13;
+
+const HEADER_SIZE: usize = 8;
+const TABLE_ENTRY_SIZE: usize = 9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Mesh,
+    Image,
+    Animation,
+    Scene
+}
+impl Kind {
+    #[allow(dead_code, reason = "only the compiler writes files")]
+    pub const fn id(&self) -> u8 {
+        match self {
+            Kind::Mesh => b'M',
+            Kind::Image => b'I',
+            Kind::Animation => b'A',
+            Kind::Scene => b'S'
+        }
+    }
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            b'M' => Some(Kind::Mesh),
+            b'I' => Some(Kind::Image),
+            b'A' => Some(Kind::Animation),
+            b'S' => Some(Kind::Scene),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    Vertices,
+    Indices,
+    Skeleton,
+    Materials,
+    Metadata,
+    Pixels,
+    Channels,
+    Submeshes,
+    Nodes,
+    Lights,
+    Cameras,
+    Lods,
+    Targets,
+    PositionDeltas,
+    NormalDeltas,
+    Weights
+}
+impl Section {
+    pub const fn id(&self) -> u8 {
+        match self {
+            Section::Vertices => 1,
+            Section::Indices => 2,
+            Section::Skeleton => 3,
+            Section::Materials => 4,
+            Section::Metadata => 5,
+            Section::Pixels => 6,
+            Section::Channels => 8,
+            Section::Submeshes => 9,
+            Section::Nodes => 10,
+            Section::Lights => 11,
+            Section::Cameras => 12,
+            Section::Lods => 13,
+            Section::Targets => 14,
+            Section::PositionDeltas => 15,
+            Section::NormalDeltas => 16,
+            Section::Weights => 17
+        }
+    }
+    #[allow(dead_code, reason = "read by compile --inspect")]
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Section::Vertices),
+            2 => Some(Section::Indices),
+            3 => Some(Section::Skeleton),
+            4 => Some(Section::Materials),
+            5 => Some(Section::Metadata),
+            6 => Some(Section::Pixels),
+            8 => Some(Section::Channels),
+            9 => Some(Section::Submeshes),
+            10 => Some(Section::Nodes),
+            11 => Some(Section::Lights),
+            12 => Some(Section::Cameras),
+            13 => Some(Section::Lods),
+            14 => Some(Section::Targets),
+            15 => Some(Section::PositionDeltas),
+            16 => Some(Section::NormalDeltas),
+            17 => Some(Section::Weights),
+            _ => None
+        }
+    }
+}
+
+/// How the pixels of an `I` file are stored and sampled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureKind {
+    /// sRGB color with linear alpha.
+    Color,
+    /// Linear RGBA data like roughness or metalness maps.
+    Linear,
+    /// Tangent space normal xy, z is reconstructed when sampling.
+    Normal,
+    /// Single linear channel.
+    Mask
+}
+impl TextureKind {
+    #[allow(dead_code, reason = "only the compiler writes files")]
+    pub const fn id(&self) -> u8 {
+        match self {
+            TextureKind::Color => 0,
+            TextureKind::Linear => 1,
+            TextureKind::Normal => 2,
+            TextureKind::Mask => 3
+        }
+    }
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(TextureKind::Color),
+            1 => Some(TextureKind::Linear),
+            2 => Some(TextureKind::Normal),
+            3 => Some(TextureKind::Mask),
+            _ => None
+        }
+    }
+    /// Stored bytes per pixel.
+    pub const fn channels(&self) -> usize {
+        match self {
+            TextureKind::Color | TextureKind::Linear => 4,
+            TextureKind::Normal => 2,
+            TextureKind::Mask => 1
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureCompression {
+    None,
+    /// BC7, BC5 or BC4 blocks depending on the `TextureKind`.
+    Bc
+}
+impl TextureCompression {
+    #[allow(dead_code, reason = "only the compiler writes files")]
+    pub const fn id(&self) -> u8 {
+        match self {
+            TextureCompression::None => 0,
+            TextureCompression::Bc => 1
+        }
+    }
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(TextureCompression::None),
+            1 => Some(TextureCompression::Bc),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    InvalidMagic,
+    NewerVersion(u16),
+    OlderVersion(u16),
+    InvalidKind(u8),
+    WrongKind { expected: Kind, found: Kind },
+    InvalidSectionTable,
+    MissingSection(Section),
+    Truncated(Section),
+    Invalid(String)
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::InvalidMagic => write!(f, "not a .low file"),
+            Error::NewerVersion(v) => write!(f, "format version {} is newer than the supported version {}, update the game", v, VERSION),
+            Error::OlderVersion(v) => write!(f, "format version {} is older than the supported version {}, recompile the assets", v, VERSION),
+            Error::InvalidKind(v) => write!(f, "unknown asset kind {:?}", *v as char),
+            Error::WrongKind { expected, found } => write!(f, "expected a {:?} file, found a {:?} file", expected, found),
+            Error::InvalidSectionTable => write!(f, "section table points outside of the file"),
+            Error::MissingSection(s) => write!(f, "missing {:?} section", s),
+            Error::Truncated(s) => write!(f, "{:?} section is truncated", s),
+            Error::Invalid(e) => write!(f, "{}", e)
+        }
+    }
+}
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Parsed `.low` file: an 8 byte header (magic, version, kind, section count),
+/// a table of `(id, offset, length)` entries and the section payloads.
+pub struct File {
+    pub kind: Kind,
+    #[allow(dead_code, reason = "read by compile --inspect")]
+    pub version: u16,
+    sections: Vec<(u8, Vec<u8>)>
+}
+impl File {
+    pub fn read(path: impl AsRef<Path>, kind: Kind) -> Result<Self, Error> {
+        let file = Self::parse(std::fs::read(path.as_ref())?)?;
+        if file.kind != kind {
+            return Err(Error::WrongKind { expected: kind, found: file.kind })
+        }
+        Ok(file)
+    }
+    pub fn parse(b: Vec<u8>) -> Result<Self, Error> {
+        if b.len() < HEADER_SIZE || b[0..4] != MAGIC { return Err(Error::InvalidMagic) }
+        let version = u16::from_be_bytes([b[4], b[5]]);
+        if version > VERSION { return Err(Error::NewerVersion(version)) }
+        if version < VERSION { return Err(Error::OlderVersion(version)) }
+        let kind = Kind::from_id(b[6]).ok_or(Error::InvalidKind(b[6]))?;
+        let sections_length = b[7] as usize;
+        if b.len() < HEADER_SIZE + sections_length * TABLE_ENTRY_SIZE { return Err(Error::InvalidSectionTable) }
+        let mut sections = Vec::with_capacity(sections_length);
+        for i in 0..sections_length {
+            let e = HEADER_SIZE + i * TABLE_ENTRY_SIZE;
+            let offset = u32::from_be_bytes([b[e+1], b[e+2], b[e+3], b[e+4]]) as usize;
+            let length = u32::from_be_bytes([b[e+5], b[e+6], b[e+7], b[e+8]]) as usize;
+            match offset.checked_add(length) {
+                Some(end) if end <= b.len() => sections.push((b[e], b[offset..end].to_vec())),
+                _ => return Err(Error::InvalidSectionTable)
+            }
+        }
+        Ok(Self { kind, version, sections })
+    }
+    pub fn section(&self, section: Section) -> Option<Cursor> {
+        self.sections.iter()
+            .find(|(id, _)| *id == section.id())
+            .map(|(_, data)| Cursor::new(data.clone()))
+    }
+    pub fn require(&self, section: Section) -> Result<Cursor, Error> {
+        self.section(section).ok_or(Error::MissingSection(section))
+    }
+    /// Id and byte length of every section in file order.
+    #[allow(dead_code, reason = "read by compile --inspect")]
+    pub fn section_lengths(&self) -> Vec<(u8, usize)> {
+        self.sections.iter().map(|(id, data)| (*id, data.len())).collect()
+    }
+    #[allow(dead_code, reason = "read by compile --inspect")]
+    pub fn metadata(&self) -> Vec<(String, String)> {
+        let mut cursor = match self.section(Section::Metadata) {
+            Some(v) => v,
+            None => return vec![]
+        };
+        let length = cursor.read_u16() as usize;
+        (0..length).map(|_| (cursor.read_str(), cursor.read_str())).collect()
+    }
+}
+
+/// Builds a `.low` file section by section, sections are written in insertion order.
+#[allow(dead_code, reason = "only the compiler writes files")]
+pub struct Writer {
+    kind: Kind,
+    sections: Vec<(Section, Vec<u8>)>
+}
+impl Writer {
+    #[allow(dead_code, reason = "only the compiler writes files")]
+    pub fn new(kind: Kind) -> Self {
+        Self { kind, sections: vec![] }
+    }
+    #[allow(dead_code, reason = "only the compiler writes files")]
+    pub fn section(&mut self, section: Section, data: Vec<u8>) {
+        self.sections.push((section, data));
+    }
+    #[allow(dead_code, reason = "only the compiler writes files")]
+    pub fn metadata(&mut self, entries: &[(&str, &str)]) {
+        let mut b = Vec::new();
+        b.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        for (key, value) in entries {
+            append_str(&mut b, key);
+            append_str(&mut b, value);
+        }
+        self.section(Section::Metadata, b);
+    }
+    #[allow(dead_code, reason = "only the compiler writes files")]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut b = Vec::new();
+        b.extend_from_slice(&MAGIC);
+        b.extend_from_slice(&VERSION.to_be_bytes());
+        b.push(self.kind.id());
+        b.push(self.sections.len() as u8);
+        let mut offset = HEADER_SIZE + self.sections.len() * TABLE_ENTRY_SIZE;
+        for (section, data) in &self.sections {
+            b.push(section.id());
+            b.extend_from_slice(&(offset as u32).to_be_bytes());
+            b.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            offset += data.len();
+        }
+        for (_, data) in &self.sections {
+            b.extend_from_slice(data);
+        }
+        b
+    }
+    #[allow(dead_code, reason = "only the compiler writes files")]
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+}
+
+/// First item whose parent is out of range or whose ancestors loop back without reaching a root.
+pub fn invalid_parent(parents: &[Option<usize>]) -> Option<usize> {
+    // 1 while on the current walk, 2 once known to reach a root
+    let mut state = vec![0u8; parents.len()];
+    let mut walk = Vec::new();
+    for id in 0..parents.len() {
+        let mut next = Some(id);
+        while let Some(v) = next {
+            if v >= parents.len() || state[v] == 1 { return Some(id) }
+            if state[v] == 2 { break }
+            state[v] = 1;
+            walk.push(v);
+            next = parents[v];
+        }
+        for v in walk.drain(..) {
+            state[v] = 2;
+        }
+    }
+    None
+}
+
+/// Strings are stored as a big endian u16 byte length followed by UTF-8 bytes.
+#[allow(dead_code, reason = "only the compiler writes files")]
+pub fn append_str(b: &mut Vec<u8>, v: &str) {
+    b.extend_from_slice(&(v.len() as u16).to_be_bytes());
+    b.extend_from_slice(v.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parents_must_reach_a_root() {
+        assert_eq!(invalid_parent(&[]), None);
+        // Children may be stored before their parents
+        assert_eq!(invalid_parent(&[Some(2), None, Some(1), Some(0)]), None);
+        assert_eq!(invalid_parent(&[None, Some(1)]), Some(1));
+        assert_eq!(invalid_parent(&[None, Some(2), Some(3), Some(1)]), Some(1));
+        // Joints below a cycle are reported too
+        assert_eq!(invalid_parent(&[Some(1), Some(2), Some(1)]), Some(0));
+        assert_eq!(invalid_parent(&[None, Some(5)]), Some(1));
+    }
+}
@@ -15,12 +15,20 @@ mod vertex;
 mod mesh;
 mod camera;
 mod texture;
+mod bc;
 mod instances;
 mod skeleton;
+mod morph;
 mod animation;
+mod keyframes;
+mod material;
+mod nodes;
+mod scene;
 mod cursor;
+mod low;
 mod transform;
 mod depth_texture;
+mod reload;
 
 use instances::InstanceTransform;
 
@@ -42,27 +50,27 @@ fn main() {
     
     let basic = shaders::basic::Shader::new(&device, surface_configuration.format);
     let basic_anim = shaders::basic_anim::Shader::new(&device, surface_configuration.format);
+    let textured = shaders::textured::Shader::new(&device, surface_configuration.format);
+    let normal_map = shaders::normal_map::Shader::new(&device, surface_configuration.format);
+    let normal_map_anim = shaders::normal_map_anim::Shader::new(&device, surface_configuration.format);
 
     let mut ground = mesh::Mesh::load(
-        &device, "./.compiled/models/shapes/cube.low",
-        shaders::basic::Material::new(&device, [0.1;4]),
-        vec![ InstanceTransform { position: [0.;3], scale: [10.,0.01,10.] } ], None
+        &device, &queue, "./.compiled/models/shapes/cube.low",
+        Some(vec![shaders::basic::Material::new(&device, [0.1;4])]),
+        vec![ InstanceTransform { position: [0.;3], scale: [10.,0.01,10.], rotation: [0.,0.,0.,1.] } ]
     );
     
-    let character_texture = texture::Texture::from(&device, &queue, "./.compiled/models/mutant/Mutant_diffuse.low");
     let mut character = mesh::Mesh::load(
-        &device, "./.compiled/models/mutant/mesh.low",
-        shaders::basic_anim::Material::new(&device, character_texture, [1.;4]),
-        vec![ InstanceTransform { position: [0.;3], scale: [0.01;3] } ],
-        Some(|s|s.replace("mixamorig:", "").replace("_", "").to_lowercase())
+        &device, &queue, "./.compiled/models/mutant/mesh.low", None,
+        vec![ InstanceTransform { position: [0.;3], scale: [0.01;3], rotation: [0.,0.,0.,1.] } ]
     );
-    let anim = animation::Animation::load("./.compiled/animations/mutant/walk.low",
-        Some(|s|s.replace("mixamorig:", "").replace("_", "").to_lowercase()));
+    let mut anim = animation::Animation::load("./.compiled/animations/mutant/walk.low");
 
-    let mut cur_frame = 0;
-    let max_frames = anim.frames - 1;
+    let anim_start = std::time::Instant::now();
     let j = 0;
 
+    let mut reloader = if cfg!(debug_assertions) { Some(reload::Reloader::new()) } else { None };
+
     event_loop.run(move |event, _, control_flow| {
         match event {
             Event::WindowEvent { event, .. } => match event {
@@ -119,9 +127,15 @@ fn main() {
                 };
                 let view = output_texture.texture.create_view(&wgpu::TextureViewDescriptor::default());
                 let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                if let Some(reloader) = reloader.as_mut() {
+                    for path in reloader.changed() {
+                        character.reload(&device, &queue, &path);
+                        ground.reload(&device, &queue, &path);
+                        anim.reload(&path);
+                    }
+                }
                 camera.update(&queue);
-                character.set_animation_pose(&anim, cur_frame);
-                if cur_frame < max_frames { cur_frame += 1 } else { cur_frame = 0 }
+                character.set_animation_pose(&anim, anim.looped(anim_start.elapsed().as_secs_f32()));
                 {
                     let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         label: None,
@@ -143,23 +157,45 @@ fn main() {
                         })
                     });
                     for mesh in vec![&mut character, &mut ground] {
+                        mesh.select_lods(camera.position);
                         mesh.update(&device, &queue);
-                        match &mesh.material {
-                            shaders::Material::BasicAnim(material) => {
-                                render_pass.set_pipeline(&basic_anim.render_pipeline);
-                                render_pass.set_bind_group(1, &material.bind_group, &[]);
-                                render_pass.set_bind_group(2, &mesh.skeleton.as_ref().unwrap().bind_group, &[]);
-                                render_pass.set_bind_group(3, &material.texture.bind_group, &[]);
-                            },
-                            shaders::Material::Basic(material) => {
-                                render_pass.set_pipeline(&basic.render_pipeline);
-                                render_pass.set_bind_group(1, &material.bind_group, &[]);
-                            }
-                        }
                         render_pass.set_bind_group(0, &camera.bind_group, &[]);
                         render_pass.set_vertex_buffer(0, mesh.vertices_buffer.slice(..));
-                        render_pass.set_vertex_buffer(1, mesh.instances.buffer.slice(..));
-                        render_pass.draw(0..mesh.vertices_len, 0..mesh.instances.buffer_len);
+                        render_pass.set_index_buffer(mesh.indices_buffer.slice(..), mesh.index_format);
+                        for submesh in &mesh.submeshes {
+                            let instances = submesh.instances.as_ref().unwrap_or(&mesh.instances);
+                            if instances.buffer_len == 0 { continue }
+                            render_pass.set_vertex_buffer(1, instances.buffer.slice(..));
+                            match mesh.material(submesh) {
+                                shaders::Material::BasicAnim(material) => {
+                                    render_pass.set_pipeline(&basic_anim.render_pipeline);
+                                    render_pass.set_bind_group(1, &material.bind_group, &[]);
+                                    render_pass.set_bind_group(2, &mesh.skeleton.as_ref().unwrap().bind_group, &[]);
+                                    render_pass.set_bind_group(3, &material.texture.bind_group, &[]);
+                                },
+                                shaders::Material::Basic(material) => {
+                                    render_pass.set_pipeline(&basic.render_pipeline);
+                                    render_pass.set_bind_group(1, &material.bind_group, &[]);
+                                }
+                                shaders::Material::Textured(material) => {
+                                    render_pass.set_pipeline(&textured.render_pipeline);
+                                    render_pass.set_bind_group(1, &material.bind_group, &[]);
+                                    render_pass.set_bind_group(2, &material.texture.bind_group, &[]);
+                                }
+                                shaders::Material::NormalMap(material) => {
+                                    render_pass.set_pipeline(&normal_map.render_pipeline);
+                                    render_pass.set_bind_group(1, &material.bind_group, &[]);
+                                }
+                                shaders::Material::NormalMapAnim(material) => {
+                                    render_pass.set_pipeline(&normal_map_anim.render_pipeline);
+                                    render_pass.set_bind_group(1, &material.bind_group, &[]);
+                                    render_pass.set_bind_group(2, &mesh.skeleton.as_ref().unwrap().bind_group, &[]);
+                                }
+                            }
+                            for (lod, range) in instances.lods.iter().enumerate().filter(|v| !v.1.is_empty()) {
+                                render_pass.draw_indexed(submesh.indices(lod), 0, range.clone());
+                            }
+                        }
                     }
                 }
                 queue.submit(std::iter::once(encoder.finish()));
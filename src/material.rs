@@ -0,0 +1,112 @@
+use crate::{cursor::Cursor, low::{self, Section}};
+
+/// Same modes as glTF materials.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlphaMode { Opaque, Mask, Blend }
+impl AlphaMode {
+    #[allow(dead_code, reason = "only the compiler writes materials")]
+    pub const fn id(&self) -> u8 {
+        match self {
+            AlphaMode::Opaque => 0,
+            AlphaMode::Mask => 1,
+            AlphaMode::Blend => 2
+        }
+    }
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(AlphaMode::Opaque),
+            1 => Some(AlphaMode::Mask),
+            2 => Some(AlphaMode::Blend),
+            _ => None
+        }
+    }
+}
+
+/// glTF metallic-roughness material of a submesh slot, textures are paths of compiled `I` files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialInfo {
+    pub name: String,
+    /// Linear RGBA multiplied with the base color texture.
+    pub base_color: [f32;4],
+    pub base_color_texture: Option<String>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Roughness in green and metalness in blue.
+    pub metallic_roughness_texture: Option<String>,
+    pub normal_texture: Option<String>,
+    pub normal_scale: f32,
+    /// Occlusion in red.
+    pub occlusion_texture: Option<String>,
+    pub occlusion_strength: f32,
+    pub emissive: [f32;3],
+    pub emissive_texture: Option<String>,
+    pub alpha_mode: AlphaMode,
+    /// Alpha below which masked materials are discarded.
+    pub alpha_cutoff: f32
+}
+impl Default for MaterialInfo {
+    /// The glTF default material.
+    fn default() -> Self {
+        Self {
+            name: "default".into(),
+            base_color: [1.;4],
+            base_color_texture: None,
+            metallic: 1.,
+            roughness: 1.,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.,
+            occlusion_texture: None,
+            occlusion_strength: 1.,
+            emissive: [0.;3],
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5
+        }
+    }
+}
+impl MaterialInfo {
+    pub fn read(cursor: &mut Cursor) -> Result<Self, low::Error> {
+        let texture = |cursor: &mut Cursor| Some(cursor.read_str()).filter(|v| !v.is_empty());
+        let name = cursor.read_str();
+        let base_color = cursor.read_vec4();
+        let base_color_texture = texture(cursor);
+        let metallic = cursor.read_f32();
+        let roughness = cursor.read_f32();
+        let metallic_roughness_texture = texture(cursor);
+        let normal_texture = texture(cursor);
+        let normal_scale = cursor.read_f32();
+        let occlusion_texture = texture(cursor);
+        let occlusion_strength = cursor.read_f32();
+        let emissive = cursor.read_vec3();
+        let emissive_texture = texture(cursor);
+        let alpha_mode_id = cursor.read_u8();
+        let alpha_cutoff = cursor.read_f32();
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Materials)) }
+        let alpha_mode = AlphaMode::from_id(alpha_mode_id).ok_or_else(|| low::Error::Invalid(format!("Invalid alpha mode: {}", alpha_mode_id)))?;
+        Ok(Self {
+            name, base_color, base_color_texture, metallic, roughness, metallic_roughness_texture, normal_texture, normal_scale,
+            occlusion_texture, occlusion_strength, emissive, emissive_texture, alpha_mode, alpha_cutoff
+        })
+    }
+    #[allow(dead_code, reason = "only the compiler writes materials")]
+    pub fn write(&self, b: &mut Vec<u8>) {
+        let texture = |b: &mut Vec<u8>, v: &Option<String>| low::append_str(b, v.as_deref().unwrap_or(""));
+        let floats = |b: &mut Vec<u8>, v: &[f32]| for c in v {
+            b.extend_from_slice(&c.to_be_bytes());
+        };
+        low::append_str(b, &self.name);
+        floats(b, &self.base_color);
+        texture(b, &self.base_color_texture);
+        floats(b, &[self.metallic, self.roughness]);
+        texture(b, &self.metallic_roughness_texture);
+        texture(b, &self.normal_texture);
+        floats(b, &[self.normal_scale]);
+        texture(b, &self.occlusion_texture);
+        floats(b, &[self.occlusion_strength]);
+        floats(b, &self.emissive);
+        texture(b, &self.emissive_texture);
+        b.push(self.alpha_mode.id());
+        floats(b, &[self.alpha_cutoff]);
+    }
+}
@@ -1,123 +1,417 @@
-use std::path::Path;
+use std::{path::{Path, PathBuf}, ops::Range};
 use wgpu::util::DeviceExt;
-use crate::{vertex::{self, VertexType}, skeleton::{Skeleton, Joint}, animation::Animation};
+use cgmath::{InnerSpace, Rotation, Vector3};
+use crate::{vertex::{self, VertexType}, skeleton::{Skeleton, Joint}, morph::{Morph, Targets}, animation::Animation, material::MaterialInfo, low::{self, Section}};
 
 pub struct Mesh {
+    pub path: PathBuf,
     pub vertices_buffer: wgpu::Buffer,
     pub vertices_len: u32,
-    pub material: crate::shaders::Material,
+    pub indices_buffer: wgpu::Buffer,
+    pub indices_len: u32,
+    pub index_format: wgpu::IndexFormat,
+    /// One material per slot, or a single material used by every slot.
+    pub materials: Vec<crate::shaders::Material>,
+    /// Materials compiled into the file, one per slot.
+    pub material_slots: Vec<MaterialInfo>,
+    pub submeshes: Vec<Submesh>,
     pub instances: crate::instances::Instances,
-    pub skeleton: Option<crate::skeleton::Skeleton>
+    pub skeleton: Option<crate::skeleton::Skeleton>,
+    pub bounds: Option<Bounds>,
+    /// Camera distance to the bounds from which each level of detail after the full one is drawn.
+    pub lod_distances: Vec<f32>,
+    /// The materials were built from `material_slots` and are rebuilt on reload.
+    file_materials: bool
+}
+
+/// Range of the vertex and index buffers drawn with one material, a glTF primitive.
+pub struct Submesh {
+    pub name: String,
+    /// Material slot index.
+    pub material_slot: usize,
+    pub first_index: u32,
+    pub indices_len: u32,
+    pub first_vertex: u32,
+    pub vertices_len: u32,
+    /// Instances of this submesh only, None draws the mesh instances.
+    pub instances: Option<crate::instances::Instances>,
+    /// Index ranges of the levels of detail after the full one.
+    pub lods: Vec<Range<u32>>
+}
+impl Submesh {
+    /// Index range of a level of detail, levels past the last one draw the last one.
+    pub fn indices(&self, lod: usize) -> Range<u32> {
+        match lod.min(self.lods.len()) {
+            0 => self.first_index..self.first_index + self.indices_len,
+            v => self.lods[v - 1].clone()
+        }
+    }
+}
+
+/// Bounding sphere of the vertices in mesh space.
+#[derive(Clone, Copy, Debug)]
+pub struct Bounds {
+    pub center: [f32;3],
+    pub radius: f32
+}
+
+struct Geometry {
+    vertices_buffer: wgpu::Buffer,
+    vertices_len: u32,
+    indices_buffer: wgpu::Buffer,
+    indices_len: u32,
+    index_format: wgpu::IndexFormat,
+    material_slots: Vec<MaterialInfo>,
+    submeshes: Vec<Submesh>,
+    /// Built from the material slots when no materials were given.
+    materials: Option<Vec<crate::shaders::Material>>,
+    joints: Option<Vec<Joint>>,
+    targets: Targets,
+    bounds: Option<Bounds>,
+    lod_distances: Vec<f32>
 }
 
-#[allow(dead_code)]
 impl Mesh {
+    /// Loads a compiled mesh drawn with `materials`, `None` builds the materials compiled into the file.
     pub fn load(
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         path: impl AsRef<Path>,
-        material: crate::shaders::Material,
-        transforms: Vec<crate::instances::InstanceTransform>,
-        rename_skeleton_joints: Option<fn(String)->String>
+        materials: Option<Vec<crate::shaders::Material>>,
+        transforms: Vec<crate::instances::InstanceTransform>
     ) -> Self {
-        let mut cursor = crate::cursor::Cursor::new(match std::fs::read(path.as_ref()) {
+        match Self::read(device, queue, path.as_ref(), materials, transforms) {
             Ok(v) => v,
             Err(e) => panic!("Error reading file: {}, {}", path.as_ref().display(), e)
-        });
-        if cursor.read_u8() != b'M' { panic!("Invalid file format") }
-
-        let vertex_type = VertexType::from(cursor.read_str().as_str());
-        if !vertex_type.compatible(&material) {
-            panic!("Mesh VertexType is not compatible with this material")
         }
-
-        let mut total_vertices = 0;
-
-        let vertices_buffer = match vertex_type {
-            VertexType::Basic => {
-                get_vertices_buffer_from_cursor::<vertex::Basic>(device, &mut cursor, &mut total_vertices, |cursor| {
-                    vertex::Basic { position: cursor.read_vec3() }
-                })
-            }
-            VertexType::NJW => {
-                get_vertices_buffer_from_cursor::<vertex::NJW>(device, &mut cursor, &mut total_vertices, |cursor| {
-                    vertex::NJW {
-                        position: cursor.read_vec3(),
-                        normal: cursor.read_vec3(),
-                        uv: cursor.read_vec2(),
-                        joints: cursor.read_joints(),
-                        weights: cursor.read_vec4()
+    }
+    pub fn read(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        materials: Option<Vec<crate::shaders::Material>>,
+        transforms: Vec<crate::instances::InstanceTransform>
+    ) -> Result<Self, low::Error> {
+        let geometry = read(device, queue, path.as_ref(), materials.as_deref())?;
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            vertices_buffer: geometry.vertices_buffer,
+            vertices_len: geometry.vertices_len,
+            indices_buffer: geometry.indices_buffer,
+            indices_len: geometry.indices_len,
+            index_format: geometry.index_format,
+            file_materials: materials.is_none(),
+            materials: materials.or(geometry.materials).unwrap(),
+            material_slots: geometry.material_slots,
+            submeshes: geometry.submeshes,
+            instances: crate::instances::Instances::new(device, transforms),
+            skeleton: geometry.joints.map(|joints| Skeleton::new(device, joints, Morph::new(device, geometry.targets))),
+            bounds: geometry.bounds,
+            lod_distances: geometry.lod_distances
+        })
+    }
+    /// Reloads the mesh or its material textures if `path` is one of them,
+    /// an invalid file is logged and the current GPU data is kept.
+    pub fn reload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, path: &Path) {
+        if path == self.path {
+            let materials = if self.file_materials { None } else { Some(self.materials.as_slice()) };
+            match read(device, queue, path, materials) {
+                Ok(geometry) => {
+                    self.vertices_buffer = geometry.vertices_buffer;
+                    self.vertices_len = geometry.vertices_len;
+                    self.indices_buffer = geometry.indices_buffer;
+                    self.indices_len = geometry.indices_len;
+                    self.index_format = geometry.index_format;
+                    self.material_slots = geometry.material_slots;
+                    self.submeshes = geometry.submeshes;
+                    if let Some(materials) = geometry.materials {
+                        self.materials = materials;
                     }
-                })
-            }
-        };
-
-        let skeleton = match vertex_type {
-            VertexType::NJW => {
-                let joints_length = cursor.read_u8() as usize;
-                if joints_length >= crate::skeleton::MAX_JOINTS {
-                    panic!("Skeleton can not have more than {} joints", crate::skeleton::MAX_JOINTS)
+                    self.skeleton = geometry.joints.map(|joints| Skeleton::new(device, joints, Morph::new(device, geometry.targets)));
+                    self.bounds = geometry.bounds;
+                    self.lod_distances = geometry.lod_distances;
+                    log::info!("Reloaded mesh: {}", path.display());
                 }
-                let mut joints = Vec::with_capacity(joints_length);
-                let mut joint_id = 0;
-                while joint_id < joints_length {
-                    let name = match rename_skeleton_joints {
-                        Some(v) => v(cursor.read_str()),
-                        None => cursor.read_str()
-                    };
-                    let parent = cursor.read_u8();
-                    let tpose = cursor.read_mat4x4();
-                    let ibm = cursor.read_mat4x4();
-                    joints.push(Joint::new(name, parent, tpose, ibm));
-                    joint_id += 1;
-                }
-                Some(Skeleton::new(device, joints))
+                Err(e) => log::error!("Error reloading mesh: {}, {}", path.display(), e)
             }
-            _ => None
+        }
+        for material in &mut self.materials {
+            material.reload(device, queue, path);
+        }
+    }
+    /// Material the submesh is drawn with.
+    pub fn material(&self, submesh: &Submesh) -> &crate::shaders::Material {
+        &self.materials[submesh.material_slot.min(self.materials.len() - 1)]
+    }
+    /// Sorts the instances by the level of detail they are drawn with from a camera at `eye`.
+    pub fn select_lods(&mut self, eye: [f32;3]) {
+        let Some(bounds) = self.bounds else { return };
+        let levels = self.submeshes.iter().map(|v| v.lods.len()).max().unwrap_or(0).min(self.lod_distances.len()) + 1;
+        if levels == 1 { return }
+        let distances = &self.lod_distances;
+        let lod = |transform: &crate::instances::InstanceTransform| {
+            let rotation = crate::keyframes::array_to_quaternion(transform.rotation);
+            let center = Vector3::from(transform.position)
+                + rotation.rotate_vector(Vector3::from(bounds.center).zip(Vector3::from(transform.scale), |a, b| a * b));
+            let radius = bounds.radius * transform.scale.iter().fold(0., |a: f32, b| a.max(b.abs()));
+            let distance = ((Vector3::from(eye) - center).magnitude() - radius).max(0.);
+            distances.iter().take_while(|v| **v <= distance).count()
         };
-        Self {
-            vertices_buffer,
-            vertices_len: total_vertices as u32,
-            material,
-            instances: crate::instances::Instances::new(device, transforms),
-            skeleton
+        self.instances.sort_lods(levels, lod);
+        for instances in self.submeshes.iter_mut().filter_map(|v| v.instances.as_mut()) {
+            instances.sort_lods(levels, lod);
         }
     }
     pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
         if let Some(skeleton) = &mut self.skeleton {
-            skeleton.update(&queue);
+            skeleton.update(queue);
+        }
+        self.instances.update(device);
+        for instances in self.submeshes.iter_mut().filter_map(|v| v.instances.as_mut()) {
+            instances.update(device);
         }
-        self.instances.update(&device);
     }
-    pub fn set_animation_pose(&mut self, animation: &Animation, frame: usize) {
+    /// Poses the skeleton with `animation` sampled at `time` seconds.
+    pub fn set_animation_pose(&mut self, animation: &Animation, time: f32) {
         if let Some(skeleton) = self.skeleton.as_mut() {
             for joint in &mut skeleton.joints {
-                if let Some(frames) = animation.joints.get(&joint.name) {
-                    joint.local_anim_pose = Some(frames[frame]);
+                if let Some(track) = animation.joints.get(&joint.name) {
+                    joint.local_anim_pose = Some(track.sample(time));
                 }
             }
+            let mut weights = Vec::new();
+            for (mesh, track) in &animation.weights {
+                weights.resize(track.targets, 0.);
+                track.sample(time, &mut weights);
+                skeleton.morph.set_mesh_weights(mesh, &weights);
+            }
         }
     }
-    pub fn joint<'a>(&'a mut self, id: usize) -> &'a mut Joint {
+    /// Sets the weight of the morph targets named `name`.
+    #[allow(dead_code, reason = "morph API, the demo plays the weights of its animation")]
+    pub fn set_weight(&mut self, name: &str, weight: f32) {
+        if let Some(skeleton) = self.skeleton.as_mut() {
+            skeleton.morph.set_weight(name, weight);
+        }
+    }
+    pub fn joint(&mut self, id: usize) -> &mut Joint {
         &mut self.skeleton.as_mut().unwrap().joints[id]
     }
 }
 
+fn read(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    path: &Path,
+    materials: Option<&[crate::shaders::Material]>
+) -> Result<Geometry, low::Error> {
+    let file = low::File::read(path, low::Kind::Mesh)?;
+    let mut cursor = file.require(Section::Vertices)?;
+
+    let vertex_type = VertexType::try_from(cursor.read_str().as_str()).map_err(low::Error::Invalid)?;
+    if !materials.unwrap_or_default().iter().all(|v| vertex_type.compatible(v)) {
+        return Err(low::Error::Invalid("Mesh VertexType is not compatible with this material".into()))
+    }
+
+    let (vertices_buffer, vertices_len) = match vertex_type {
+        VertexType::Basic => {
+            get_vertices_buffer_from_cursor::<vertex::Basic>(device, &mut cursor, |cursor| {
+                vertex::Basic { position: cursor.read_vec3() }
+            })?
+        }
+        VertexType::N => {
+            get_vertices_buffer_from_cursor::<vertex::N>(device, &mut cursor, |cursor| {
+                vertex::N {
+                    position: cursor.read_vec3(),
+                    normal: cursor.read_vec3(),
+                    uv: cursor.read_vec2()
+                }
+            })?
+        }
+        VertexType::NJW => {
+            get_vertices_buffer_from_cursor::<vertex::NJW>(device, &mut cursor, |cursor| {
+                vertex::NJW {
+                    position: cursor.read_vec3(),
+                    normal: cursor.read_vec3(),
+                    uv: cursor.read_vec2(),
+                    joints: cursor.read_joints(),
+                    weights: cursor.read_vec4()
+                }
+            })?
+        }
+        VertexType::NT => {
+            get_vertices_buffer_from_cursor::<vertex::NT>(device, &mut cursor, |cursor| {
+                vertex::NT {
+                    position: cursor.read_vec3(),
+                    normal: cursor.read_vec3(),
+                    tangent: cursor.read_vec4(),
+                    uv: cursor.read_vec2()
+                }
+            })?
+        }
+        VertexType::NTJW => {
+            get_vertices_buffer_from_cursor::<vertex::NTJW>(device, &mut cursor, |cursor| {
+                vertex::NTJW {
+                    position: cursor.read_vec3(),
+                    normal: cursor.read_vec3(),
+                    tangent: cursor.read_vec4(),
+                    uv: cursor.read_vec2(),
+                    joints: cursor.read_joints(),
+                    weights: cursor.read_vec4()
+                }
+            })?
+        }
+    };
+
+    let (indices_buffer, indices_len, index_format) =
+        get_indices_buffer_from_cursor(device, &mut file.require(Section::Indices)?, vertices_len)?;
+
+    let joints = match vertex_type {
+        VertexType::NJW | VertexType::NTJW => {
+            let mut cursor = file.require(Section::Skeleton)?;
+            let joints_length = cursor.read_u16() as usize;
+            let mut joints = Vec::with_capacity(joints_length);
+            let mut joint_id = 0;
+            while joint_id < joints_length {
+                let name = cursor.read_str();
+                let parent = match cursor.read_u16() {
+                    u16::MAX => None,
+                    v if (v as usize) < joints_length => Some(v as usize),
+                    v => return Err(low::Error::Invalid(format!("Joint {} has an invalid parent {}", name, v)))
+                };
+                let tpose = cursor.read_mat4x4();
+                let ibm = cursor.read_mat4x4();
+                joints.push(Joint::new(name, parent, tpose, ibm));
+                joint_id += 1;
+            }
+            if cursor.overflowed() { return Err(low::Error::Truncated(Section::Skeleton)) }
+            let parents: Vec<Option<usize>> = joints.iter().map(|v| v.parent_id).collect();
+            if let Some(id) = low::invalid_parent(&parents) {
+                return Err(low::Error::Invalid(format!("Joint {} has a cycle in its parents", joints[id].name)))
+            }
+            Some(joints)
+        }
+        _ => None
+    };
+    let targets = Targets::read(&file, vertices_len)?;
+    let mut cursor = file.require(Section::Materials)?;
+    let material_slots = (0..cursor.read_u16()).map(|_| MaterialInfo::read(&mut cursor)).collect::<Result<Vec<_>, _>>()?;
+    let mut submeshes = read_submeshes(&mut file.require(Section::Submeshes)?, vertices_len, indices_len, material_slots.len())?;
+    let (bounds, lod_distances) = match file.section(Section::Lods) {
+        Some(mut cursor) => {
+            let (bounds, lod_distances) = read_lods(&mut cursor, &mut submeshes, indices_len)?;
+            (Some(bounds), lod_distances)
+        }
+        None => (None, Vec::new())
+    };
+    let materials = match materials {
+        Some(v) if v.len() != 1 && v.len() != material_slots.len() => {
+            return Err(low::Error::Invalid(format!("Mesh has {} material slots but {} materials were given", material_slots.len(), v.len())))
+        }
+        Some(_) => None,
+        None => Some(material_slots.iter().map(|v| crate::shaders::Material::from_info(device, queue, &vertex_type, v)).collect::<Result<Vec<_>, _>>()?)
+    };
+    Ok(Geometry { vertices_buffer, vertices_len, indices_buffer, indices_len, index_format, material_slots, submeshes, materials, joints, targets, bounds, lod_distances })
+}
+
+/// Submeshes whose ranges must be inside the buffers and slots inside the material slots.
+fn read_submeshes(cursor: &mut crate::cursor::Cursor, vertices_len: u32, indices_len: u32, slots_length: usize) -> Result<Vec<Submesh>, low::Error> {
+    let submeshes_length = cursor.read_u16() as usize;
+    let mut submeshes = Vec::with_capacity(submeshes_length);
+    for _ in 0..submeshes_length {
+        let submesh = Submesh {
+            name: cursor.read_str(),
+            material_slot: cursor.read_u16() as usize,
+            first_index: cursor.read_u32(),
+            indices_len: cursor.read_u32(),
+            first_vertex: cursor.read_u32(),
+            vertices_len: cursor.read_u32(),
+            instances: None,
+            lods: Vec::new()
+        };
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Submeshes)) }
+        if submesh.material_slot >= slots_length
+            || submesh.first_index as u64 + submesh.indices_len as u64 > indices_len as u64
+            || submesh.first_vertex as u64 + submesh.vertices_len as u64 > vertices_len as u64 {
+            return Err(low::Error::Invalid(format!("Submesh {} is out of range", submesh.name)))
+        }
+        submeshes.push(submesh);
+    }
+    if cursor.overflowed() { return Err(low::Error::Truncated(Section::Submeshes)) }
+    Ok(submeshes)
+}
+
+/// Bounds and levels of detail, each level holding a distance and an index range per submesh.
+fn read_lods(cursor: &mut crate::cursor::Cursor, submeshes: &mut [Submesh], indices_len: u32) -> Result<(Bounds, Vec<f32>), low::Error> {
+    let bounds = Bounds { center: cursor.read_vec3(), radius: cursor.read_f32() };
+    let levels = cursor.read_u8() as usize;
+    let mut distances = Vec::with_capacity(levels);
+    for _ in 0..levels {
+        distances.push(cursor.read_f32());
+        for submesh in submeshes.iter_mut() {
+            let (first, len) = (cursor.read_u32(), cursor.read_u32());
+            if first as u64 + len as u64 > indices_len as u64 {
+                return Err(low::Error::Invalid(format!("Level of detail {} of submesh {} is out of range", distances.len(), submesh.name)))
+            }
+            submesh.lods.push(first..first + len);
+        }
+    }
+    if cursor.overflowed() { return Err(low::Error::Truncated(Section::Lods)) }
+    Ok((bounds, distances))
+}
+
 #[inline]
 fn get_vertices_buffer_from_cursor<V: Copy + Clone + bytemuck::Pod + bytemuck::Zeroable>(
     device: &wgpu::Device,
     cursor: &mut crate::cursor::Cursor,
-    total_vertices: &mut usize,
     f: fn(&mut crate::cursor::Cursor) -> V
-) -> wgpu::Buffer {
-    let mut vertices = Vec::<V>::new();
+) -> Result<(wgpu::Buffer, u32), low::Error> {
     let vertices_length = cursor.read_u32() as usize;
+    if vertices_length > cursor.remaining() { return Err(low::Error::Truncated(Section::Vertices)) }
+    let mut vertices = Vec::<V>::with_capacity(vertices_length);
     for _ in 0..vertices_length {
         vertices.push(f(cursor));
     }
-    *total_vertices = vertices_length;
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    if cursor.overflowed() { return Err(low::Error::Truncated(Section::Vertices)) }
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: None,
         contents: bytemuck::cast_slice(&vertices),
         usage: wgpu::BufferUsages::VERTEX
-    })
-}
\ No newline at end of file
+    });
+    Ok((buffer, vertices_length as u32))
+}
+
+#[inline]
+fn get_indices_buffer_from_cursor(
+    device: &wgpu::Device,
+    cursor: &mut crate::cursor::Cursor,
+    vertices_len: u32
+) -> Result<(wgpu::Buffer, u32, wgpu::IndexFormat), low::Error> {
+    let index_size = cursor.read_u8();
+    let indices_length = cursor.read_u32();
+    if indices_length as usize > cursor.remaining() { return Err(low::Error::Truncated(Section::Indices)) }
+    let (contents, format) = match index_size {
+        2 => {
+            let indices: Vec<u16> = (0..indices_length).map(|_| cursor.read_u16()).collect();
+            if indices.iter().any(|i| *i as u32 >= vertices_len) {
+                return Err(low::Error::Invalid("Index out of range".into()))
+            }
+            (bytemuck::cast_slice(&indices).to_vec(), wgpu::IndexFormat::Uint16)
+        }
+        4 => {
+            let indices: Vec<u32> = (0..indices_length).map(|_| cursor.read_u32()).collect();
+            if indices.iter().any(|i| *i >= vertices_len) {
+                return Err(low::Error::Invalid("Index out of range".into()))
+            }
+            (bytemuck::cast_slice(&indices).to_vec(), wgpu::IndexFormat::Uint32)
+        }
+        _ => return Err(low::Error::Invalid(format!("Invalid index size: {}", index_size)))
+    };
+    if cursor.overflowed() { return Err(low::Error::Truncated(Section::Indices)) }
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: &contents,
+        usage: wgpu::BufferUsages::INDEX
+    });
+    Ok((buffer, indices_length, format))
+}
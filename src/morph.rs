@@ -0,0 +1,94 @@
+use wgpu::util::DeviceExt;
+
+use crate::low::{self, Section};
+
+pub struct Target {
+    /// glTF mesh the target belongs to, animation weights are matched by it.
+    pub mesh: String,
+    pub name: String,
+    pub weight: f32
+}
+
+/// Morph targets read from a mesh file, a position and a normal delta per target and vertex.
+#[derive(Default)]
+pub struct Targets {
+    pub targets: Vec<Target>,
+    /// Target major, position then normal of each vertex.
+    pub deltas: Vec<[[f32;4];2]>,
+    pub vertices: u32
+}
+impl Targets {
+    /// No targets when the file has no Targets section.
+    pub fn read(file: &low::File, vertices: u32) -> Result<Self, low::Error> {
+        let Some(mut cursor) = file.section(Section::Targets) else { return Ok(Self::default()) };
+        let targets_length = cursor.read_u16() as usize;
+        let mut targets = Vec::with_capacity(targets_length);
+        for _ in 0..targets_length {
+            targets.push(Target { mesh: cursor.read_str(), name: cursor.read_str(), weight: cursor.read_f32() });
+        }
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Targets)) }
+        let length = targets_length * vertices as usize;
+        let mut deltas = vec![[[0.;4];2]; length];
+        for (section, k) in [(Section::PositionDeltas, 0), (Section::NormalDeltas, 1)] {
+            let mut cursor = file.require(section)?;
+            if cursor.remaining() != length * 12 {
+                return Err(low::Error::Invalid(format!("{:?} has {} bytes for {} targets of {} vertices", section, cursor.remaining(), targets_length, vertices)))
+            }
+            for delta in &mut deltas {
+                let v = cursor.read_vec3();
+                delta[k] = [v[0], v[1], v[2], 0.];
+            }
+        }
+        Ok(Self { targets, deltas, vertices })
+    }
+}
+
+/// Target weights and deltas bound with the skeleton, the skinned shaders add them before skinning.
+pub struct Morph {
+    pub targets: Vec<Target>,
+    pub weights: Vec<f32>,
+    /// Storage buffer with the target and vertex counts followed by the weights.
+    pub weights_buffer: wgpu::Buffer,
+    /// Storage buffer with the deltas, one unused delta when there are no targets.
+    pub deltas_buffer: wgpu::Buffer
+}
+impl Morph {
+    pub fn new(device: &wgpu::Device, targets: Targets) -> Self {
+        let weights: Vec<f32> = targets.targets.iter().map(|v| v.weight).collect();
+        let mut contents = Vec::with_capacity(8 + weights.len().max(1) * 4);
+        for v in [weights.len() as u32, targets.vertices] {
+            contents.extend_from_slice(&v.to_ne_bytes());
+        }
+        // A storage binding can not be empty, like the joints
+        contents.extend_from_slice(bytemuck::cast_slice(if weights.is_empty() { &[0.] } else { &weights }));
+        let weights_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &contents,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST
+        });
+        let deltas = if targets.deltas.is_empty() { vec![[[0.;4];2]] } else { targets.deltas };
+        let deltas_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&deltas),
+            usage: wgpu::BufferUsages::STORAGE
+        });
+        Self { targets: targets.targets, weights, weights_buffer, deltas_buffer }
+    }
+    pub fn update(&self, queue: &wgpu::Queue) {
+        if self.weights.is_empty() { return }
+        queue.write_buffer(&self.weights_buffer, 8, bytemuck::cast_slice(&self.weights));
+    }
+    /// Sets the weight of every target named `name`.
+    pub fn set_weight(&mut self, name: &str, weight: f32) {
+        for (target, v) in self.targets.iter().zip(&mut self.weights) {
+            if target.name == name { *v = weight }
+        }
+    }
+    /// Sets the weights of the targets of `mesh` in order, like a glTF weights channel.
+    pub fn set_mesh_weights(&mut self, mesh: &str, weights: &[f32]) {
+        let targets = self.targets.iter().zip(&mut self.weights).filter(|(target, _)| target.mesh == mesh);
+        for ((_, v), weight) in targets.zip(weights) {
+            *v = *weight;
+        }
+    }
+}
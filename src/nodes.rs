@@ -0,0 +1,142 @@
+use std::ops::Range;
+use crate::{cursor::Cursor, keyframes::Trs, low::{self, Section}};
+
+/// Node of an `S` scene file, parents are stored before their children.
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub local: Trs,
+    /// Submeshes of the scene mesh drawn at this node, a glTF mesh.
+    pub submeshes: Range<usize>,
+    /// glTF skin index, also set for meshes the compiler bound to a joint. These nodes are placed by their joints.
+    pub skin: Option<usize>,
+    pub camera: Option<usize>,
+    pub light: Option<usize>
+}
+impl Node {
+    pub fn read(cursor: &mut Cursor) -> Result<Self, low::Error> {
+        let name = cursor.read_str();
+        let parent = read_index(cursor);
+        let local = Trs { translation: cursor.read_vec3(), rotation: cursor.read_vec4(), scale: cursor.read_vec3() };
+        let first_submesh = cursor.read_u16() as usize;
+        let submeshes = first_submesh..first_submesh + cursor.read_u16() as usize;
+        let (skin, camera, light) = (read_index(cursor), read_index(cursor), read_index(cursor));
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Nodes)) }
+        Ok(Self { name, parent, local, submeshes, skin, camera, light })
+    }
+    #[allow(dead_code, reason = "only the compiler writes scenes")]
+    pub fn write(&self, b: &mut Vec<u8>) {
+        low::append_str(b, &self.name);
+        write_index(b, self.parent);
+        for c in self.local.translation.iter().chain(&self.local.rotation).chain(&self.local.scale) {
+            b.extend_from_slice(&c.to_be_bytes());
+        }
+        b.extend_from_slice(&(self.submeshes.start as u16).to_be_bytes());
+        b.extend_from_slice(&(self.submeshes.len() as u16).to_be_bytes());
+        for v in [self.skin, self.camera, self.light] {
+            write_index(b, v);
+        }
+    }
+}
+
+/// `KHR_lights_punctual` types, lights shine along the node -Z axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightKind { Directional, Point, Spot }
+impl LightKind {
+    #[allow(dead_code, reason = "only the compiler writes scenes")]
+    pub const fn id(&self) -> u8 {
+        match self {
+            LightKind::Directional => 0,
+            LightKind::Point => 1,
+            LightKind::Spot => 2
+        }
+    }
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(LightKind::Directional),
+            1 => Some(LightKind::Point),
+            2 => Some(LightKind::Spot),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Light {
+    pub name: String,
+    pub kind: LightKind,
+    /// Linear RGB.
+    pub color: [f32;3],
+    /// Lux for directional lights and candela otherwise.
+    pub intensity: f32,
+    /// None is infinite.
+    pub range: Option<f32>,
+    /// Spot cone angles in radians.
+    pub inner_cone: f32,
+    pub outer_cone: f32
+}
+impl Light {
+    pub fn read(cursor: &mut Cursor) -> Result<Self, low::Error> {
+        let name = cursor.read_str();
+        let kind_id = cursor.read_u8();
+        let kind = LightKind::from_id(kind_id).ok_or_else(|| low::Error::Invalid(format!("Invalid light kind: {}", kind_id)))?;
+        let color = cursor.read_vec3();
+        let intensity = cursor.read_f32();
+        let range = Some(cursor.read_f32()).filter(|v| *v > 0.);
+        let (inner_cone, outer_cone) = (cursor.read_f32(), cursor.read_f32());
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Lights)) }
+        Ok(Self { name, kind, color, intensity, range, inner_cone, outer_cone })
+    }
+    #[allow(dead_code, reason = "only the compiler writes scenes")]
+    pub fn write(&self, b: &mut Vec<u8>) {
+        low::append_str(b, &self.name);
+        b.push(self.kind.id());
+        for c in self.color.iter().chain(&[self.intensity, self.range.unwrap_or(0.), self.inner_cone, self.outer_cone]) {
+            b.extend_from_slice(&c.to_be_bytes());
+        }
+    }
+}
+
+/// glTF camera looking along the node -Z axis.
+#[derive(Clone, Copy, Debug)]
+pub enum Camera {
+    /// `yfov` in radians, None aspect uses the viewport and None `zfar` is infinite.
+    Perspective { yfov: f32, aspect: Option<f32>, znear: f32, zfar: Option<f32> },
+    Orthographic { xmag: f32, ymag: f32, znear: f32, zfar: f32 }
+}
+impl Camera {
+    pub fn read(cursor: &mut Cursor) -> Result<Self, low::Error> {
+        let kind = cursor.read_u8();
+        let v = [cursor.read_f32(), cursor.read_f32(), cursor.read_f32(), cursor.read_f32()];
+        if cursor.overflowed() { return Err(low::Error::Truncated(Section::Cameras)) }
+        let positive = |v: f32| Some(v).filter(|v| *v > 0.);
+        match kind {
+            0 => Ok(Camera::Perspective { yfov: v[0], aspect: positive(v[1]), znear: v[2], zfar: positive(v[3]) }),
+            1 => Ok(Camera::Orthographic { xmag: v[0], ymag: v[1], znear: v[2], zfar: v[3] }),
+            _ => Err(low::Error::Invalid(format!("Invalid camera projection: {}", kind)))
+        }
+    }
+    #[allow(dead_code, reason = "only the compiler writes scenes")]
+    pub fn write(&self, b: &mut Vec<u8>) {
+        let (kind, v) = match *self {
+            Camera::Perspective { yfov, aspect, znear, zfar } => (0, [yfov, aspect.unwrap_or(0.), znear, zfar.unwrap_or(0.)]),
+            Camera::Orthographic { xmag, ymag, znear, zfar } => (1, [xmag, ymag, znear, zfar])
+        };
+        b.push(kind);
+        for c in v {
+            b.extend_from_slice(&c.to_be_bytes());
+        }
+    }
+}
+
+/// Indices are stored as u16, `u16::MAX` being None.
+fn read_index(cursor: &mut Cursor) -> Option<usize> {
+    match cursor.read_u16() {
+        u16::MAX => None,
+        v => Some(v as usize)
+    }
+}
+fn write_index(b: &mut Vec<u8>, v: Option<usize>) {
+    b.extend_from_slice(&v.map_or(u16::MAX, |v| v as u16).to_be_bytes());
+}
@@ -0,0 +1,51 @@
+use std::{path::{Path, PathBuf}, collections::HashMap, time::{Duration, Instant, SystemTime}, fs};
+
+pub const COMPILED: &str = "./.compiled/";
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Dev-only watcher for compiled `.low` files, the game polls it every frame
+/// and hands the changed paths to `Mesh::reload` and `Animation::reload`.
+pub struct Reloader {
+    modified: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant
+}
+impl Reloader {
+    pub fn new() -> Self {
+        let mut modified = HashMap::new();
+        scan(Path::new(COMPILED), &mut modified);
+        Self {
+            modified,
+            last_poll: Instant::now()
+        }
+    }
+    /// Files created or modified since the last call, checked at most once per `POLL_INTERVAL`.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < POLL_INTERVAL { return vec![] }
+        self.last_poll = Instant::now();
+        let mut modified = HashMap::with_capacity(self.modified.len());
+        scan(Path::new(COMPILED), &mut modified);
+        let changed = modified.iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(*time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        self.modified = modified;
+        changed
+    }
+}
+
+fn scan(path: &Path, res: &mut HashMap<PathBuf, SystemTime>) {
+    let entries = match fs::read_dir(path) {
+        Ok(v) => v,
+        Err(_) => return
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            scan(&path, res)
+        } else if path.extension().is_some_and(|v| v == "low") {
+            if let Ok(modified) = entry.metadata().and_then(|v| v.modified()) {
+                res.insert(path, modified);
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use cgmath::{Matrix4, SquareMatrix};
+use crate::{
+    instances::{Instances, InstanceTransform}, keyframes::Trs, low::{self, Section}, mesh::Mesh,
+    nodes::{Camera, Light, Node}
+};
+
+/// Compiled glTF node hierarchy, the mesh submeshes are instanced at every node drawing them.
+pub struct Scene {
+    pub path: PathBuf,
+    pub mesh: Mesh,
+    pub nodes: Vec<Node>,
+    /// Node transforms relative to the scene root.
+    pub world: Vec<Matrix4<f32>>,
+    pub lights: Vec<Light>,
+    pub cameras: Vec<Camera>,
+    /// Every copy of the hierarchy, one instance per node and transform.
+    transforms: Vec<InstanceTransform>
+}
+
+struct Data {
+    mesh_path: PathBuf,
+    nodes: Vec<Node>,
+    lights: Vec<Light>,
+    cameras: Vec<Camera>
+}
+
+impl Scene {
+    /// Loads a compiled scene and its mesh, with the materials compiled into the mesh.
+    #[allow(dead_code, reason = "engine API, the demo draws single meshes")]
+    pub fn load(device: &wgpu::Device, queue: &wgpu::Queue, path: impl AsRef<Path>, transforms: Vec<InstanceTransform>) -> Self {
+        match Self::read(device, queue, path.as_ref(), transforms) {
+            Ok(v) => v,
+            Err(e) => panic!("Error reading file: {}, {}", path.as_ref().display(), e)
+        }
+    }
+    #[allow(dead_code, reason = "engine API, the demo draws single meshes")]
+    pub fn read(device: &wgpu::Device, queue: &wgpu::Queue, path: impl AsRef<Path>, transforms: Vec<InstanceTransform>) -> Result<Self, low::Error> {
+        let data = read(path.as_ref())?;
+        let mesh = Mesh::read(device, queue, &data.mesh_path, None, Vec::new())?;
+        let mut res = Self {
+            path: path.as_ref().to_path_buf(),
+            mesh,
+            nodes: Vec::new(),
+            world: Vec::new(),
+            lights: Vec::new(),
+            cameras: Vec::new(),
+            transforms
+        };
+        res.set_data(device, data)?;
+        Ok(res)
+    }
+    /// Reloads the scene, its mesh or a material texture if `path` is one of them,
+    /// an invalid file is logged and the current scene is kept.
+    #[allow(dead_code, reason = "engine API, the demo draws single meshes")]
+    pub fn reload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, path: &Path) {
+        if path == self.path {
+            let res = read(path).and_then(|data| {
+                if data.mesh_path != self.mesh.path {
+                    self.mesh = Mesh::read(device, queue, &data.mesh_path, None, Vec::new())?;
+                }
+                self.set_data(device, data)
+            });
+            match res {
+                Ok(()) => log::info!("Reloaded scene: {}", path.display()),
+                Err(e) => log::error!("Error reloading scene: {}, {}", path.display(), e)
+            }
+            return
+        }
+        self.mesh.reload(device, queue, path);
+        if path == self.mesh.path {
+            self.spawn(device, self.transforms.clone());
+        }
+    }
+    /// Places a copy of the hierarchy at each of `transforms`.
+    pub fn spawn(&mut self, device: &wgpu::Device, transforms: Vec<InstanceTransform>) {
+        let mut submeshes = vec![Vec::new(); self.mesh.submeshes.len()];
+        for root in &transforms {
+            let root_matrix = Trs { translation: root.position, rotation: root.rotation, scale: root.scale }.matrix();
+            for (node, world) in self.nodes.iter().zip(&self.world) {
+                if node.submeshes.is_empty() { continue }
+                // Skinned meshes are placed by their joints, glTF ignores the node transform
+                let trs = Trs::from_matrix(if node.skin.is_some() { root_matrix } else { root_matrix * world });
+                let transform = InstanceTransform { position: trs.translation, scale: trs.scale, rotation: trs.rotation };
+                for instances in &mut submeshes[node.submeshes.clone()] {
+                    instances.push(transform);
+                }
+            }
+        }
+        for (submesh, transforms) in self.mesh.submeshes.iter_mut().zip(submeshes) {
+            submesh.instances = Some(Instances::new(device, transforms));
+        }
+        self.transforms = transforms;
+    }
+    /// Index of the first node named `name`.
+    #[allow(dead_code, reason = "engine API, the demo draws single meshes")]
+    pub fn node(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|v| v.name == name)
+    }
+    fn set_data(&mut self, device: &wgpu::Device, data: Data) -> Result<(), low::Error> {
+        if let Some(node) = data.nodes.iter().find(|v| v.submeshes.end > self.mesh.submeshes.len()) {
+            return Err(low::Error::Invalid(format!("Node {} draws submeshes {:?} but the mesh has {}", node.name, node.submeshes, self.mesh.submeshes.len())))
+        }
+        let mut world: Vec<Matrix4<f32>> = Vec::with_capacity(data.nodes.len());
+        for node in &data.nodes {
+            let parent = node.parent.map_or(Matrix4::identity(), |v| world[v]);
+            world.push(parent * node.local.matrix());
+        }
+        self.nodes = data.nodes;
+        self.world = world;
+        self.lights = data.lights;
+        self.cameras = data.cameras;
+        self.spawn(device, self.transforms.clone());
+        Ok(())
+    }
+}
+
+fn read(path: &Path) -> Result<Data, low::Error> {
+    let file = low::File::read(path, low::Kind::Scene)?;
+    let mut cursor = file.require(Section::Nodes)?;
+    let mesh_path = PathBuf::from(cursor.read_str());
+    let nodes = (0..cursor.read_u16()).map(|_| Node::read(&mut cursor)).collect::<Result<Vec<_>, _>>()?;
+
+    let mut cursor = file.require(Section::Lights)?;
+    let lights = (0..cursor.read_u16()).map(|_| Light::read(&mut cursor)).collect::<Result<Vec<_>, _>>()?;
+    let mut cursor = file.require(Section::Cameras)?;
+    let cameras = (0..cursor.read_u16()).map(|_| Camera::read(&mut cursor)).collect::<Result<Vec<_>, _>>()?;
+
+    for (id, node) in nodes.iter().enumerate() {
+        if node.parent.is_some_and(|v| v >= id) {
+            return Err(low::Error::Invalid(format!("Node {} is stored before its parent", node.name)))
+        }
+        if node.light.is_some_and(|v| v >= lights.len()) || node.camera.is_some_and(|v| v >= cameras.len()) {
+            return Err(low::Error::Invalid(format!("Node {} has an invalid light or camera", node.name)))
+        }
+    }
+    Ok(Data { mesh_path, nodes, lights, cameras })
+}
@@ -8,10 +8,8 @@ pub struct MaterialBinding {
 
 pub struct Material {
     pub bind_group: wgpu::BindGroup,
-    pub buffer: wgpu::Buffer,
-    pub color: [f32;4]
+    pub buffer: wgpu::Buffer
 }
-#[allow(dead_code)]
 impl Material {
     pub fn new(
         device: &wgpu::Device,
@@ -35,9 +33,10 @@ impl Material {
             ]
         });
         crate::shaders::Material::Basic(Self {
-            buffer, bind_group, color
+            buffer, bind_group
         })
     }
+    #[allow(dead_code, reason = "material API, the demo never recolors materials")]
     pub fn _update(&mut self, queue: &Queue, color: [f32;4]) {
         queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[MaterialBinding {color}]));
     }
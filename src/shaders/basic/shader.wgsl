@@ -3,7 +3,8 @@ struct Vertex {
 };
 struct Transform {
     @location(1) position: vec3<f32>,
-    @location(2) scale: vec3<f32>
+    @location(2) scale: vec3<f32>,
+    @location(3) rotation: vec4<f32>
 };
 
 struct Camera {
@@ -13,9 +14,13 @@ struct Camera {
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
+fn rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
 @vertex
 fn vs_main(vertex: Vertex, transform: Transform) -> @builtin(position) vec4<f32> {
-    return camera.perspective * vec4<f32>((transform.scale * vertex.position) + transform.position, 1.0);
+    return camera.perspective * vec4<f32>(rotate(transform.rotation, transform.scale * vertex.position) + transform.position, 1.0);
 }
 
 struct Material {
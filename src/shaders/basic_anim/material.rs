@@ -3,26 +3,29 @@ use wgpu::{util::DeviceExt, Queue};
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct MaterialBinding {
-    pub color: [f32;4]
+    pub color: [f32;4],
+    pub alpha_cutoff: f32,
+    pub _padding: [f32;3]
 }
 
-#[allow(dead_code)]
 pub struct Material {
     pub bind_group: wgpu::BindGroup,
     pub buffer: wgpu::Buffer,
     pub texture: crate::texture::Texture,
-    pub color: [f32;4]
+    /// Texels with a lower alpha are discarded, 0 keeps every texel.
+    pub alpha_cutoff: f32
 }
 impl Material {
     pub fn new(
         device: &wgpu::Device,
         texture: crate::texture::Texture,
-        color: [f32;4]
+        color: [f32;4],
+        alpha_cutoff: f32
     ) -> crate::shaders::Material {
         let buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: None,
-                contents: bytemuck::cast_slice(&[MaterialBinding {color}]),
+                contents: bytemuck::cast_slice(&[MaterialBinding { color, alpha_cutoff, _padding: [0.;3] }]),
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
             }
         );
@@ -37,11 +40,12 @@ impl Material {
             ]
         });
         crate::shaders::Material::BasicAnim(Self {
-            buffer, bind_group, texture, color
+            buffer, bind_group, texture, alpha_cutoff
         })
     }
+    #[allow(dead_code, reason = "material API, the demo never recolors materials")]
     pub fn _update(&mut self, queue: &Queue, color: [f32;4]) {
-        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[MaterialBinding {color}]));
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[MaterialBinding { color, alpha_cutoff: self.alpha_cutoff, _padding: [0.;3] }]));
     }
 }
 
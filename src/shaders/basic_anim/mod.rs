@@ -33,7 +33,7 @@ impl Shader {
                     wgpu::VertexBufferLayout {
                         array_stride: std::mem::size_of::<crate::instances::InstanceTransform>() as wgpu::BufferAddress,
                         step_mode: wgpu::VertexStepMode::Instance,
-                        attributes: &wgpu::vertex_attr_array![5 => Float32x3, 6 => Float32x3]
+                        attributes: &wgpu::vertex_attr_array![5 => Float32x3, 6 => Float32x3, 7 => Float32x4]
                     }
                 ]
             },
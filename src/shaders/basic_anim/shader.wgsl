@@ -7,7 +7,8 @@ struct Vertex {
 };
 struct Transform {
     @location(5) position: vec3<f32>,
-    @location(6) scale: vec3<f32>
+    @location(6) scale: vec3<f32>,
+    @location(7) rotation: vec4<f32>
 };
 
 struct Camera {
@@ -17,11 +18,21 @@ struct Camera {
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
-struct Skin {
-    @location(0) pose: array<mat4x4<f32>,64>
-};
+// One matrix per joint of the skeleton
 @group(2) @binding(0)
-var<uniform> skin: Skin;
+var<storage, read> skin: array<mat4x4<f32>>;
+
+// Target and vertex counts followed by one weight per morph target
+struct Morph {
+    targets: u32,
+    vertices: u32,
+    weights: array<f32>
+};
+@group(2) @binding(1)
+var<storage, read> morph: Morph;
+// Position then normal delta of every target and vertex
+@group(2) @binding(2)
+var<storage, read> deltas: array<vec4<f32>>;
 
 struct Output {
     @builtin(position) position: vec4<f32>,
@@ -31,25 +42,41 @@ struct Output {
 
 fn apply_skin(vertex: Vertex, v3: vec3<f32>) -> vec3<f32> {
     let v4 = vec4<f32>(v3, 1.0);
-    var res = ((skin.pose[vertex.joints[0]] * v4) * vertex.weights[0]);
-    if (vertex.joints[1] != 255u) { res += ((skin.pose[vertex.joints[1]] * v4) * vertex.weights[1]); }
-    if (vertex.joints[2] != 255u) { res += ((skin.pose[vertex.joints[2]] * v4) * vertex.weights[2]); }
-    if (vertex.joints[3] != 255u) { res += ((skin.pose[vertex.joints[3]] * v4) * vertex.weights[3]); }
+    var res = vec4<f32>(0.0);
+    for (var i = 0; i < 4; i = i + 1) {
+        if (vertex.weights[i] > 0.0) { res += ((skin[vertex.joints[i]] * v4) * vertex.weights[i]); }
+    }
     return res.xyz;
 }
 
+// k is 0 for positions and 1 for normals
+fn apply_morph(index: u32, v3: vec3<f32>, k: u32) -> vec3<f32> {
+    var res = v3;
+    for (var t = 0u; t < morph.targets; t = t + 1u) {
+        let weight = morph.weights[t];
+        if (weight != 0.0) { res += deltas[(t * morph.vertices + index) * 2u + k].xyz * weight; }
+    }
+    return res;
+}
+
+fn rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
 @vertex
-fn vs_main(vertex: Vertex, transform: Transform) -> Output {
+fn vs_main(vertex: Vertex, transform: Transform, @builtin(vertex_index) index: u32) -> Output {
     var out: Output;
     out.uv = vertex.uv;
-    out.position = camera.perspective * vec4<f32>((apply_skin(vertex, vertex.position) * transform.scale), 1.0);
-    out.normal = (camera.perspective * vec4<f32>(apply_skin(vertex, vertex.normal), 1.0)).xyz;
+    let position = apply_skin(vertex, apply_morph(index, vertex.position, 0u));
+    out.position = camera.perspective * vec4<f32>(rotate(transform.rotation, position * transform.scale) + transform.position, 1.0);
+    out.normal = (camera.perspective * vec4<f32>(rotate(transform.rotation, apply_skin(vertex, apply_morph(index, vertex.normal, 1u))), 1.0)).xyz;
     return out;
 }
 
 
 struct Material {
-    @location(0) color: vec4<f32>
+    @location(0) color: vec4<f32>,
+    @location(1) alpha_cutoff: f32
 }
 @group(1) @binding(0)
 var<uniform> material: Material;
@@ -61,8 +88,11 @@ var s_diffuse: sampler;
 
 @fragment
 fn fs_main(in: Output) -> @location(0) vec4<f32> {
-    let texture = textureSample(t_diffuse, s_diffuse, in.uv);
+    let texture = textureSample(t_diffuse, s_diffuse, in.uv) * material.color;
+    if (texture.a < material.alpha_cutoff) {
+        discard;
+    }
     let dot = dot(normalize(vec3<f32>(0.0,0.0,1.0)), normalize(in.normal));
     let shadow = (dot + 1.0) / 2.0;
-    return texture * vec4<f32>(material.color.xyz * shadow, 1.0);
+    return vec4<f32>(texture.rgb * shadow, texture.a);
 }
\ No newline at end of file
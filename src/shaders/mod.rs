@@ -1,7 +1,105 @@
 pub mod basic_anim;
 pub mod basic;
+pub mod normal_map;
+pub mod normal_map_anim;
+pub mod textured;
 
 pub enum Material {
     BasicAnim(basic_anim::Material),
-    Basic(basic::Material)
-}
\ No newline at end of file
+    Basic(basic::Material),
+    Textured(textured::Material),
+    NormalMap(normal_map::Material),
+    NormalMapAnim(normal_map::Material)
+}
+impl Material {
+    /// Builds the material a submesh of `vertex_type` is drawn with, the shaders use the
+    /// base color, its texture, the alpha cutoff of masked materials and the normal map.
+    pub fn from_info(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertex_type: &crate::vertex::VertexType,
+        info: &crate::material::MaterialInfo
+    ) -> Result<Self, crate::low::Error> {
+        let texture = |path: &Option<String>, default: fn(&wgpu::Device, &wgpu::Queue) -> crate::texture::Texture| match path {
+            Some(path) => crate::texture::Texture::read(device, queue, path)
+                .map_err(|e| crate::low::Error::Invalid(format!("Material {} texture {}, {}", info.name, path, e))),
+            None => Ok(default(device, queue))
+        };
+        let alpha_cutoff = match info.alpha_mode {
+            crate::material::AlphaMode::Opaque => 0.,
+            crate::material::AlphaMode::Mask => info.alpha_cutoff,
+            // Blending is not supported, blended materials are masked
+            crate::material::AlphaMode::Blend => {
+                log::warn!("Material {} uses Blend alpha, drawn masked at alpha cutoff {}", info.name, info.alpha_cutoff);
+                info.alpha_cutoff
+            }
+        };
+        // Only the base color and normal textures are drawn, each by the vertex types that have the attributes
+        let base_color_texture = if matches!(vertex_type, crate::vertex::VertexType::Basic) { &info.base_color_texture } else { &None };
+        let normal_texture = match vertex_type {
+            crate::vertex::VertexType::NT | crate::vertex::VertexType::NTJW => &None,
+            _ => &info.normal_texture
+        };
+        let ignored: Vec<&str> = [base_color_texture, &info.metallic_roughness_texture, normal_texture, &info.occlusion_texture, &info.emissive_texture]
+            .into_iter().flatten().map(|v| v.as_str()).collect();
+        if !ignored.is_empty() {
+            log::warn!("Material {} textures are not used by the shaders: {}", info.name, ignored.join(", "));
+        }
+        Ok(match vertex_type {
+            crate::vertex::VertexType::Basic => basic::Material::new(device, info.base_color),
+            crate::vertex::VertexType::N => {
+                let base_color = texture(&info.base_color_texture, crate::texture::Texture::white)?;
+                Material::Textured(textured::Material::new(device, base_color, info.base_color, alpha_cutoff))
+            }
+            crate::vertex::VertexType::NJW => {
+                let base_color = texture(&info.base_color_texture, crate::texture::Texture::white)?;
+                basic_anim::Material::new(device, base_color, info.base_color, alpha_cutoff)
+            }
+            crate::vertex::VertexType::NT | crate::vertex::VertexType::NTJW => {
+                let normal_texture = texture(&info.normal_texture, crate::texture::Texture::flat_normal)?;
+                if normal_texture.kind != crate::low::TextureKind::Normal {
+                    return Err(crate::low::Error::Invalid(format!("Material {} normal texture {} is not compiled as Normal", info.name, normal_texture.path.display())))
+                }
+                let base_color = texture(&info.base_color_texture, crate::texture::Texture::white)?;
+                let material = normal_map::Material::new(device, base_color, normal_texture, info.base_color, alpha_cutoff, info.normal_scale);
+                match vertex_type {
+                    crate::vertex::VertexType::NT => Material::NormalMap(material),
+                    _ => Material::NormalMapAnim(material)
+                }
+            }
+        })
+    }
+    pub fn reload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, path: &std::path::Path) {
+        match self {
+            Material::BasicAnim(material) => {
+                material.texture.reload(device, queue, path);
+            }
+            Material::Textured(material) => {
+                material.texture.reload(device, queue, path);
+            }
+            Material::NormalMap(material) | Material::NormalMapAnim(material) => material.reload(device, queue, path),
+            Material::Basic(_) => {}
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    /// Validates every shader like wgpu does when building the pipelines at startup.
+    #[test]
+    fn shaders_validate() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/shaders");
+        let mut shaders = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path().join("shader.wgsl");
+            if !path.is_file() { continue }
+            let source = std::fs::read_to_string(&path).unwrap();
+            let module = naga::front::wgsl::parse_str(&source)
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e.emit_to_string(&source)));
+            naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+                .validate(&module)
+                .unwrap_or_else(|e| panic!("{}: {:?}", path.display(), e));
+            shaders += 1;
+        }
+        assert_eq!(shaders, 5);
+    }
+}
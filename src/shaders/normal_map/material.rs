@@ -0,0 +1,126 @@
+use wgpu::{util::DeviceExt, Queue};
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialBinding {
+    pub color: [f32;4],
+    pub alpha_cutoff: f32,
+    pub normal_scale: f32,
+    pub _padding: [f32;2]
+}
+
+/// Base color and tangent space normal map, shared by the static and skinned normal map shaders.
+pub struct Material {
+    pub bind_group: wgpu::BindGroup,
+    pub buffer: wgpu::Buffer,
+    pub texture: crate::texture::Texture,
+    /// `Normal` texture, xy in red and green.
+    pub normal_texture: crate::texture::Texture,
+    /// Texels with a lower alpha are discarded, 0 keeps every texel.
+    pub alpha_cutoff: f32,
+    /// Multiplies the x and y of the sampled normals.
+    pub normal_scale: f32
+}
+impl Material {
+    pub fn new(
+        device: &wgpu::Device,
+        texture: crate::texture::Texture,
+        normal_texture: crate::texture::Texture,
+        color: [f32;4],
+        alpha_cutoff: f32,
+        normal_scale: f32
+    ) -> Self {
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(&[MaterialBinding { color, alpha_cutoff, normal_scale, _padding: [0.;2] }]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+        let bind_group = bind_group(device, &buffer, &texture, &normal_texture);
+        Self {
+            buffer, bind_group, texture, normal_texture, alpha_cutoff, normal_scale
+        }
+    }
+    #[allow(dead_code, reason = "material API, the demo never recolors materials")]
+    pub fn _update(&mut self, queue: &Queue, color: [f32;4]) {
+        let binding = MaterialBinding { color, alpha_cutoff: self.alpha_cutoff, normal_scale: self.normal_scale, _padding: [0.;2] };
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[binding]));
+    }
+    /// Reloads the textures and rebuilds the bind group if `path` is one of them.
+    pub fn reload(&mut self, device: &wgpu::Device, queue: &Queue, path: &std::path::Path) {
+        let texture = self.texture.reload(device, queue, path);
+        if texture | self.normal_texture.reload(device, queue, path) {
+            self.bind_group = bind_group(device, &self.buffer, &self.texture, &self.normal_texture);
+        }
+    }
+}
+
+fn bind_group(
+    device: &wgpu::Device,
+    buffer: &wgpu::Buffer,
+    texture: &crate::texture::Texture,
+    normal_texture: &crate::texture::Texture
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &bind_group_layout(device),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding()
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&texture.view)
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler)
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&normal_texture.view)
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::Sampler(&normal_texture.sampler)
+            }
+        ]
+    })
+}
+
+pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let texture = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true }
+        },
+        count: None
+    };
+    let sampler = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None
+    };
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None
+                },
+                count: None
+            },
+            texture(1), sampler(2), texture(3), sampler(4)
+        ]
+    })
+}
@@ -0,0 +1,115 @@
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) tangent: vec4<f32>,
+    @location(3) uv: vec2<f32>,
+    @location(4) joints: vec4<u32>,
+    @location(5) weights: vec4<f32>
+};
+struct Transform {
+    @location(6) position: vec3<f32>,
+    @location(7) scale: vec3<f32>,
+    @location(8) rotation: vec4<f32>
+};
+
+struct Camera {
+    @location(0) perspective: mat4x4<f32>,
+    @location(1) position: vec4<f32>
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// One matrix per joint of the skeleton
+@group(2) @binding(0)
+var<storage, read> skin: array<mat4x4<f32>>;
+
+// Target and vertex counts followed by one weight per morph target
+struct Morph {
+    targets: u32,
+    vertices: u32,
+    weights: array<f32>
+};
+@group(2) @binding(1)
+var<storage, read> morph: Morph;
+// Position then normal delta of every target and vertex
+@group(2) @binding(2)
+var<storage, read> deltas: array<vec4<f32>>;
+
+struct Output {
+    @builtin(position) position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) tangent: vec4<f32>,
+    @location(2) uv: vec2<f32>
+};
+
+// w is 1 for points and 0 for directions
+fn apply_skin(vertex: Vertex, v4: vec4<f32>) -> vec3<f32> {
+    var res = vec4<f32>(0.0);
+    for (var i = 0; i < 4; i = i + 1) {
+        if (vertex.weights[i] > 0.0) { res += ((skin[vertex.joints[i]] * v4) * vertex.weights[i]); }
+    }
+    return res.xyz;
+}
+
+// k is 0 for positions and 1 for normals
+fn apply_morph(index: u32, v3: vec3<f32>, k: u32) -> vec3<f32> {
+    var res = v3;
+    for (var t = 0u; t < morph.targets; t = t + 1u) {
+        let weight = morph.weights[t];
+        if (weight != 0.0) { res += deltas[(t * morph.vertices + index) * 2u + k].xyz * weight; }
+    }
+    return res;
+}
+
+fn rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
+@vertex
+fn vs_main(vertex: Vertex, transform: Transform, @builtin(vertex_index) index: u32) -> Output {
+    var out: Output;
+    out.uv = vertex.uv;
+    let position = apply_skin(vertex, vec4<f32>(apply_morph(index, vertex.position, 0u), 1.0));
+    out.position = camera.perspective * vec4<f32>(rotate(transform.rotation, position * transform.scale) + transform.position, 1.0);
+    // Directions scale inversely so they stay perpendicular to the surface
+    out.normal = rotate(transform.rotation, apply_skin(vertex, vec4<f32>(apply_morph(index, vertex.normal, 1u), 0.0)) / transform.scale);
+    let tangent = apply_skin(vertex, vec4<f32>(vertex.tangent.xyz, 0.0));
+    out.tangent = vec4<f32>(rotate(transform.rotation, tangent * transform.scale), vertex.tangent.w);
+    return out;
+}
+
+
+struct Material {
+    @location(0) color: vec4<f32>,
+    @location(1) alpha_cutoff: f32,
+    @location(2) normal_scale: f32
+}
+@group(1) @binding(0)
+var<uniform> material: Material;
+@group(1) @binding(1)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(2)
+var s_diffuse: sampler;
+@group(1) @binding(3)
+var t_normal: texture_2d<f32>;
+@group(1) @binding(4)
+var s_normal: sampler;
+
+@fragment
+fn fs_main(in: Output) -> @location(0) vec4<f32> {
+    // Both textures are sampled before the discard, implicit derivatives need uniform control flow
+    let texture = textureSample(t_diffuse, s_diffuse, in.uv) * material.color;
+    let sampled_normal = textureSample(t_normal, s_normal, in.uv).rg;
+    if (texture.a < material.alpha_cutoff) {
+        discard;
+    }
+    let n = normalize(in.normal);
+    let t = normalize(in.tangent.xyz - n * dot(n, in.tangent.xyz));
+    let b = cross(n, t) * in.tangent.w;
+    let xy = (sampled_normal * 2.0 - 1.0) * material.normal_scale;
+    let z = sqrt(max(1.0 - dot(xy, xy), 0.0));
+    let normal = normalize(t * xy.x + b * xy.y + n * z);
+    let dot = dot(normalize(vec3<f32>(0.3, 1.0, 0.5)), normal);
+    let shadow = (dot + 1.0) / 2.0;
+    return vec4<f32>(texture.rgb * shadow, texture.a);
+}
@@ -0,0 +1,69 @@
+use wgpu::{util::DeviceExt, Queue};
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialBinding {
+    pub color: [f32;4],
+    pub alpha_cutoff: f32,
+    pub _padding: [f32;3]
+}
+
+/// Base color and its texture for static meshes, lit like the normal mapped materials.
+pub struct Material {
+    pub bind_group: wgpu::BindGroup,
+    pub buffer: wgpu::Buffer,
+    pub texture: crate::texture::Texture,
+    /// Texels with a lower alpha are discarded, 0 keeps every texel.
+    pub alpha_cutoff: f32
+}
+impl Material {
+    pub fn new(
+        device: &wgpu::Device,
+        texture: crate::texture::Texture,
+        color: [f32;4],
+        alpha_cutoff: f32
+    ) -> Self {
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(&[MaterialBinding { color, alpha_cutoff, _padding: [0.;3] }]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout(device),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding()
+                }
+            ]
+        });
+        Self {
+            buffer, bind_group, texture, alpha_cutoff
+        }
+    }
+    #[allow(dead_code, reason = "material API, the demo never recolors materials")]
+    pub fn _update(&mut self, queue: &Queue, color: [f32;4]) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[MaterialBinding { color, alpha_cutoff: self.alpha_cutoff, _padding: [0.;3] }]));
+    }
+}
+
+pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None
+                },
+                count: None
+            }
+        ]
+    })
+}
\ No newline at end of file
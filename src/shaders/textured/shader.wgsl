@@ -0,0 +1,61 @@
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>
+};
+struct Transform {
+    @location(3) position: vec3<f32>,
+    @location(4) scale: vec3<f32>,
+    @location(5) rotation: vec4<f32>
+};
+
+struct Camera {
+    @location(0) perspective: mat4x4<f32>,
+    @location(1) position: vec4<f32>
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Output {
+    @builtin(position) position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) uv: vec2<f32>
+};
+
+fn rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
+@vertex
+fn vs_main(vertex: Vertex, transform: Transform) -> Output {
+    var out: Output;
+    out.uv = vertex.uv;
+    out.position = camera.perspective * vec4<f32>(rotate(transform.rotation, vertex.position * transform.scale) + transform.position, 1.0);
+    // Normals scale inversely so they stay perpendicular to the surface
+    out.normal = rotate(transform.rotation, vertex.normal / transform.scale);
+    return out;
+}
+
+
+struct Material {
+    @location(0) color: vec4<f32>,
+    @location(1) alpha_cutoff: f32
+}
+@group(1) @binding(0)
+var<uniform> material: Material;
+
+@group(2) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(2) @binding(1)
+var s_diffuse: sampler;
+
+@fragment
+fn fs_main(in: Output) -> @location(0) vec4<f32> {
+    let texture = textureSample(t_diffuse, s_diffuse, in.uv) * material.color;
+    if (texture.a < material.alpha_cutoff) {
+        discard;
+    }
+    let dot = dot(normalize(vec3<f32>(0.3, 1.0, 0.5)), normalize(in.normal));
+    let shadow = (dot + 1.0) / 2.0;
+    return vec4<f32>(texture.rgb * shadow, texture.a);
+}
@@ -1,28 +1,25 @@
 use wgpu::util::DeviceExt;
 use cgmath::{Matrix4, Vector4};
 
-pub const MAX_JOINTS: usize = 64;
-
-use crate::transform::Transform;
+use crate::{transform::Transform, morph::Morph};
 
 pub struct Joint {
     pub name: String,
     pub tpose: Matrix4<f32>,
     pub ibm: Matrix4<f32>,
     pub local_anim_pose: Option<Matrix4<f32>>,
-    pub parent_id: usize,
+    pub parent_id: Option<usize>,
     pub parents: Vec<usize>,
     pub transform: Transform
 }
-#[allow(dead_code)]
 impl Joint {
-    pub fn new(name: String, parent: u8, tpose: [[f32;4];4], ibm: [[f32;4];4]) -> Self {
+    pub fn new(name: String, parent: Option<usize>, tpose: [[f32;4];4], ibm: [[f32;4];4]) -> Self {
         Joint {
             name,
             tpose: tpose.into(),
             ibm: ibm.into(),
             local_anim_pose: None,
-            parent_id: parent as usize,
+            parent_id: parent,
             parents: vec![],
             transform: Default::default()
         }
@@ -31,9 +28,10 @@ impl Joint {
     pub fn local_pose(&self, joints: &[Joint]) -> Matrix4<f32> {
         match self.local_anim_pose {
             Some(v) => v,
-            None => if self.parent_id != 255 {
-                self.tpose * joints[self.parent_id].ibm
-            }else { self.tpose }
+            None => match self.parent_id {
+                Some(parent_id) => joints[parent_id].ibm * self.tpose,
+                None => self.tpose
+            }
         }
     }
     pub fn pose(&self, joints: &[Joint]) -> Matrix4<f32> {
@@ -42,39 +40,38 @@ impl Joint {
                                  z: Vector4 { x: 0., y: 0., z: 1., w: 0. },
                                  w: Vector4 { x: 0., y: 0., z: 0., w: 1. } };
         for parent in &self.parents {
-            pose = joints[*parent].local_pose(joints) * pose;
+            pose = pose * joints[*parent].local_pose(joints);
         }
-        self.local_pose(joints) * pose
+        pose * self.local_pose(joints)
     }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct SkeletonBinding {
-    pub pose: [[[f32;4];4];MAX_JOINTS]
-}
 pub struct Skeleton {
     pub bind_group: wgpu::BindGroup,
+    /// Storage buffer with one skinning matrix per joint.
     pub buffer: wgpu::Buffer,
     pub joints: Vec<Joint>,
-    pub binding: SkeletonBinding
+    pub binding: Vec<[[f32;4];4]>,
+    /// Morph targets applied before skinning, bound with the joints.
+    pub morph: Morph
 }
 impl Skeleton {
+    /// Joint parents must lead to a root, which `mesh.rs` checks when reading the Skeleton section.
     pub fn new(
         device: &wgpu::Device,
-        mut joints: Vec<Joint>
+        mut joints: Vec<Joint>,
+        morph: Morph
     ) -> Self {
-        let binding = SkeletonBinding {
-            pose: [[ [1.,0.,0.,0.],
-                     [0.,1.,0.,0.],
-                     [0.,0.,1.,0.],
-                     [0.,0.,0.,1.] ]; MAX_JOINTS]
-        };
+        // A storage binding can not be empty, a skeleton without joints keeps one unused matrix
+        let binding = vec![[ [1.,0.,0.,0.],
+                             [0.,1.,0.,0.],
+                             [0.,0.,1.,0.],
+                             [0.,0.,0.,1.] ]; joints.len().max(1)];
         let buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: None,
-                contents: bytemuck::cast_slice(&[binding]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+                contents: bytemuck::cast_slice(&binding),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST
             }
         );
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -84,6 +81,14 @@ impl Skeleton {
                 wgpu::BindGroupEntry {
                     binding: 0,
                     resource: buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: morph.weights_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: morph.deltas_buffer.as_entire_binding()
                 }
             ]
         });
@@ -91,9 +96,9 @@ impl Skeleton {
         let joints_length = joints.len();
         while joint_root_id < joints_length {
             let mut parent_id = joints[joint_root_id].parent_id;
-            while parent_id != 255 {
-                joints[joint_root_id].parents.insert(0, parent_id);
-                parent_id = joints[parent_id].parent_id;
+            while let Some(id) = parent_id {
+                joints[joint_root_id].parents.insert(0, id);
+                parent_id = joints[id].parent_id;
             }
             joint_root_id += 1;
         }
@@ -101,34 +106,34 @@ impl Skeleton {
             buffer,
             bind_group,
             joints,
-            binding
+            binding,
+            morph
         }
     }
     pub fn update(&mut self, queue: &wgpu::Queue) {
         let mut i = 0;
         let l = self.joints.len();
         while i < l {
-            self.binding.pose[i] = (self.joints[i].pose(&self.joints) * self.joints[i].ibm).into();
+            self.binding[i] = (self.joints[i].pose(&self.joints) * self.joints[i].ibm).into();
             i += 1;
         }
-        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.binding]));
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.binding));
+        self.morph.update(queue);
     }
 }
 
 pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: None,
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None
-                },
-                count: None
-            }
-        ]
+        entries: &[0, 1, 2].map(|binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None
+            },
+            count: None
+        })
     })
 }
\ No newline at end of file
@@ -1,68 +1,137 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use crate::{bc, low::{self, Section, TextureCompression, TextureKind}};
 
 pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
 pub struct Texture {
-    pub bind_group: wgpu::BindGroup
+    pub path: PathBuf,
+    pub kind: TextureKind,
+    pub bind_group: wgpu::BindGroup,
+    /// For bind groups holding several textures.
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler
 }
 
 impl Texture {
+    #[allow(dead_code, reason = "loads textures without a material")]
     pub fn from(device: &wgpu::Device, queue: &wgpu::Queue, path: impl AsRef<Path>) -> Self {
-        let mut cursor = crate::cursor::Cursor::new(std::fs::read(path.as_ref()).unwrap());
-        if cursor.read_u8() != b'I' { panic!("Invalid file type") }
+        match Self::read(device, queue, path.as_ref()) {
+            Ok(v) => v,
+            Err(e) => panic!("Error reading file: {}, {}", path.as_ref().display(), e)
+        }
+    }
+    /// Swaps the bind group if `path` is this texture, an invalid file is logged and the current one is kept.
+    /// Returns true when the texture changed.
+    pub fn reload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, path: &Path) -> bool {
+        if path != self.path { return false }
+        match Self::read(device, queue, path) {
+            Ok(v) => {
+                *self = v;
+                log::info!("Reloaded texture: {}", path.display());
+                true
+            }
+            Err(e) => {
+                log::error!("Error reloading texture: {}, {}", path.display(), e);
+                false
+            }
+        }
+    }
+    pub fn read(device: &wgpu::Device, queue: &wgpu::Queue, path: impl AsRef<Path>) -> Result<Self, low::Error> {
+        let file = low::File::read(path.as_ref(), low::Kind::Image)?;
+        let mut cursor = file.require(Section::Pixels)?;
         let width = cursor.read_u32();
         let height = cursor.read_u32();
-
-        let mut diffuse_rgba = image::RgbaImage::new(width, height);
-        for y in 0..height {
-            for x in 0..width {
-                diffuse_rgba.put_pixel(x, y, image::Rgba([
-                    cursor.read_u8(),
-                    cursor.read_u8(),
-                    cursor.read_u8(),
-                    255
-                ]));
-            }
+        let kind_id = cursor.read_u8();
+        let kind = TextureKind::from_id(kind_id).ok_or_else(|| low::Error::Invalid(format!("Invalid texture kind: {}", kind_id)))?;
+        let compression_id = cursor.read_u8();
+        let compression = TextureCompression::from_id(compression_id).ok_or_else(|| low::Error::Invalid(format!("Invalid texture compression: {}", compression_id)))?;
+        let mip_level_count = cursor.read_u8() as u32;
+        if width == 0 || height == 0 || mip_level_count == 0 || mip_level_count > 32 - width.max(height).leading_zeros() {
+            return Err(low::Error::Invalid(format!("Invalid texture size: {}x{}, {} mip levels", width, height, mip_level_count)))
+        }
+        if compression == TextureCompression::Bc && (width % 4 != 0 || height % 4 != 0) {
+            return Err(low::Error::Invalid(format!("Block compressed texture size is not a multiple of 4: {}x{}", width, height)))
         }
-        
+        // Without BC support the blocks are decoded and uploaded uncompressed
+        let compressed = compression == TextureCompression::Bc && device.features().contains(wgpu::Features::TEXTURE_COMPRESSION_BC);
+        let format = if compressed { bc_format(kind) } else { format(kind) };
         let texture_size = wgpu::Extent3d {
             width, height,
             depth_or_array_layers: 1
         };
+        let mut levels = Vec::with_capacity(mip_level_count as usize);
+        for level in 0..mip_level_count {
+            let size = texture_size.mip_level_size(level, false);
+            let length = match compression {
+                TextureCompression::None => size.width as usize * size.height as usize * kind.channels(),
+                TextureCompression::Bc => (size.width as usize).div_ceil(4) * (size.height as usize).div_ceil(4) * bc::block_size(kind)
+            };
+            if length > cursor.remaining() { return Err(low::Error::Truncated(Section::Pixels)) }
+            let data = cursor.read_bytes(length);
+            let data = match compression {
+                TextureCompression::Bc if !compressed => bc::decode(kind, size.width, size.height, &data).map_err(low::Error::Invalid)?,
+                _ => data
+            };
+            levels.push((size, data));
+        }
+        Ok(Self::create(device, queue, path, kind, format, &levels))
+    }
+    /// 1x1 white color texture for materials without one.
+    pub fn white(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 };
+        Self::create(device, queue, "", TextureKind::Color, format(TextureKind::Color), &[(size, vec![255;4])])
+    }
+    /// 1x1 normal map pointing along the surface normal.
+    pub fn flat_normal(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 };
+        Self::create(device, queue, "", TextureKind::Normal, format(TextureKind::Normal), &[(size, vec![128;2])])
+    }
+    fn create(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        kind: TextureKind,
+        format: wgpu::TextureFormat,
+        levels: &[(wgpu::Extent3d, Vec<u8>)]
+    ) -> Self {
         let texture = device.create_texture(
             &wgpu::TextureDescriptor {
                 label: Some("Texture"),
-                size: texture_size,
-                mip_level_count: 1,
+                size: levels[0].0,
+                mip_level_count: levels.len() as u32,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                format,
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
             }
         );
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All
-            },
-            &diffuse_rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * width),
-                rows_per_image: std::num::NonZeroU32::new(height)
-            },
-            texture_size
-        );
+        let info = format.describe();
+        let (block_width, block_height) = (info.block_dimensions.0 as u32, info.block_dimensions.1 as u32);
+        for (mip_level, (size, data)) in levels.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(size.width.div_ceil(block_width) * info.block_size as u32),
+                    rows_per_image: std::num::NonZeroU32::new(size.height.div_ceil(block_height))
+                },
+                size.physical_size(format)
+            );
+        }
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
             ..Default::default()
         });
         let bind_group = device.create_bind_group(
@@ -82,11 +151,33 @@ impl Texture {
             }
         );
         Self {
-            bind_group
+            path: path.as_ref().to_path_buf(),
+            kind,
+            bind_group,
+            view: texture_view,
+            sampler
         }
     }
 }
 
+pub const fn format(kind: TextureKind) -> wgpu::TextureFormat {
+    match kind {
+        TextureKind::Color => wgpu::TextureFormat::Rgba8UnormSrgb,
+        TextureKind::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        TextureKind::Normal => wgpu::TextureFormat::Rg8Unorm,
+        TextureKind::Mask => wgpu::TextureFormat::R8Unorm
+    }
+}
+
+pub const fn bc_format(kind: TextureKind) -> wgpu::TextureFormat {
+    match kind {
+        TextureKind::Color => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+        TextureKind::Linear => wgpu::TextureFormat::Bc7RgbaUnorm,
+        TextureKind::Normal => wgpu::TextureFormat::Bc5RgUnorm,
+        TextureKind::Mask => wgpu::TextureFormat::Bc4RUnorm
+    }
+}
+
 pub fn bind_group(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: Some("texture_bind_group_layout"),
@@ -1,4 +1,4 @@
-use cgmath::{Vector3, Quaternion, Rotation3, Rad, Matrix4, Vector4};
+use cgmath::{Vector3, Quaternion, Rotation3, Rad};
 
 #[derive(Clone, Copy)]
 pub struct Transform {
@@ -6,15 +6,7 @@ pub struct Transform {
     rotation: Quaternion<f32>,
     scale: Vector3<f32>
 }
-#[allow(dead_code)]
 impl Transform {
-    pub fn new(translation: [f32;3], rotation: [f32;4], scale: [f32;3]) -> Self {
-        Self {
-            translation: translation.into(),
-            rotation: Quaternion::new(rotation[0], rotation[0], rotation[0], rotation[0]),
-            scale: scale.into()
-        }
-    }
     pub fn translate(&mut self, v: Vector3<f32>) {
         self.translation = self.translation + v
     }
@@ -26,15 +18,6 @@ impl Transform {
     pub fn scale(&mut self, v: Vector3<f32>) {
         self.scale = self.scale + v
     }
-    pub fn mat(&self) -> Matrix4<f32> {
-        Matrix4::from_nonuniform_scale(self.scale[0], self.scale[1], self.scale[2]) * (
-            Matrix4::from(self.rotation) * Matrix4::from_translation(self.translation)
-        )
-    }
-    pub fn transform(&self, v: [f32;3]) -> [f32;3] {
-        let v = self.mat() * Vector4::new(v[0], v[1], v[2], 1.);
-        [v.x, v.y, v.z]
-    }
 }
 
 impl Default for Transform {
@@ -17,41 +17,97 @@ pub struct NJW {
     pub position: [f32;3],
     pub normal: [f32;3],
     pub uv: [f32;2],
-    pub joints: [u32;4],
+    pub joints: [u16;4],
     pub weights: [f32;4]
 }
 impl NJW {
     pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
         array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
         step_mode: wgpu::VertexStepMode::Vertex,
-        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2, 3 => Uint32x4, 4 => Float32x4]
+        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2, 3 => Uint16x4, 4 => Float32x4]
     };
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct N {
+    pub position: [f32;3],
+    pub normal: [f32;3],
+    pub uv: [f32;2]
+}
+impl N {
+    pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2]
+    };
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct NT {
+    pub position: [f32;3],
+    pub normal: [f32;3],
+    /// Handedness of the bitangent in w.
+    pub tangent: [f32;4],
+    pub uv: [f32;2]
+}
+impl NT {
+    pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x4, 3 => Float32x2]
+    };
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct NTJW {
+    pub position: [f32;3],
+    pub normal: [f32;3],
+    pub tangent: [f32;4],
+    pub uv: [f32;2],
+    pub joints: [u16;4],
+    pub weights: [f32;4]
+}
+impl NTJW {
+    pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x4, 3 => Float32x2, 4 => Uint16x4, 5 => Float32x4]
+    };
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub enum VertexType {
     Basic,
-    NJW
+    N,
+    NJW,
+    NT,
+    NTJW
 }
 impl VertexType {
     pub const fn compatible(&self, v: &crate::shaders::Material) -> bool {
         match v {
-            crate::shaders::Material::Basic(_) => match self {
-                VertexType::Basic => true,
-                _ => false
-            }
-            crate::shaders::Material::BasicAnim(_) => match self {
-                VertexType::NJW => true,
-                _ => false
-            }
+            crate::shaders::Material::Basic(_) => matches!(self, VertexType::Basic),
+            crate::shaders::Material::Textured(_) => matches!(self, VertexType::N),
+            crate::shaders::Material::BasicAnim(_) => matches!(self, VertexType::NJW),
+            crate::shaders::Material::NormalMap(_) => matches!(self, VertexType::NT),
+            crate::shaders::Material::NormalMapAnim(_) => matches!(self, VertexType::NTJW)
         }
     }
 }
-impl From<&str> for VertexType {
-    fn from(v: &str) -> Self {
+impl TryFrom<&str> for VertexType {
+    type Error = String;
+    fn try_from(v: &str) -> Result<Self, Self::Error> {
         match v {
-            "Basic" => Self::Basic,
-            "NJW" => Self::NJW,
-            _ => panic!("Invalid VertexType: {}", v)
+            "Basic" => Ok(Self::Basic),
+            "N" => Ok(Self::N),
+            "NJW" => Ok(Self::NJW),
+            "NT" => Ok(Self::NT),
+            "NTJW" => Ok(Self::NTJW),
+            _ => Err(format!("Invalid VertexType: {}", v))
         }
-    }    
+    }
 }
\ No newline at end of file
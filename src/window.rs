@@ -4,7 +4,7 @@ use winit::{
     dpi::PhysicalPosition
 };
 
-#[allow(dead_code)]
+#[allow(dead_code, reason = "alternative to the borderless window")]
 pub fn new_fullscreen(event_loop: &EventLoop<()>) -> Window {
     let window = WindowBuilder::new()
         .with_title("3D Rust Game")
@@ -32,7 +32,6 @@ pub fn new_fullscreen(event_loop: &EventLoop<()>) -> Window {
     window
 }
 
-#[allow(dead_code)]
 pub fn new_borderless(event_loop: &EventLoop<()>) -> Window {
     let window = WindowBuilder::new()
         .with_title("3D Rust Game")